@@ -0,0 +1,422 @@
+use std::{fs, io::{self, Write}};
+use std::path::{Path, PathBuf};
+
+use crate::lagoon::{Lagoon, cell::Cell, expect::Expectations};
+use crate::lagoon::io::{Buffer, Input, InputMode, Io, Output, OutputMode};
+
+/// The most `;` a test may execute when no `--max-steps` is given, so that one
+/// stuck in a loop fails instead of holding up the rest.
+pub const DEFAULT_STEPS: usize = 1_000_000;
+
+/// What running a program did.
+#[derive(Debug)]
+struct Outcome {
+	output: String,
+	/// Every cell that is not zero, one `index: value` per line
+	memory: String,
+	/// Why the program could not be parsed or stopped early
+	error: Option<String>,
+	/// Every `//!` directive the program did not live up to
+	unmet: Vec<String>
+}
+
+/// A file that could not be read or written, and why.
+type FileError = (PathBuf, io::Error);
+
+/// A program and the files next to it.
+struct Files {
+	source: String,
+	output: Option<String>,
+	memory: Option<String>,
+	input: Option<String>
+}
+
+/// How a single test went.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Verdict {
+	Pass,
+	Fail,
+	Bless,
+	Skip
+}
+
+/// Runs every `.lag` program under `paths` that has a `.out` or `.mem` file
+/// next to it or `//!` directives in it, on a `Lagoon` from `new` with the
+/// `.in` file next to it as its input, or else its `//! input:`. Each result
+/// and a summary go to `out`. With `bless`, the `.out` and `.mem` files are
+/// rewritten to match instead, and programs with neither those nor directives
+/// get a `.out`. A program that cannot be read fails on its own, without
+/// stopping the rest. Returns whether every test passed.
+pub fn run<C: Cell>(
+	paths: &[String],
+	new: impl Fn() -> Lagoon<i32, C>,
+	chars: bool,
+	bless: bool,
+	out: &mut impl Write
+) -> io::Result<bool> {
+	let mut counts = [0; 4];
+	for found in discover(paths) {
+		let verdict = match found {
+			Ok(program) => test(&program, &new, chars, bless, out)?,
+			Err((path, e)) => fail(&path, (path.clone(), e), out)?
+		};
+		counts[verdict as usize] += 1;
+	}
+	let [passed, failed, blessed, skipped] = counts;
+	write!(out, "{} passed, {} failed", passed, failed)?;
+	if blessed > 0 { write!(out, ", {} blessed", blessed)?; }
+	if skipped > 0 { write!(out, ", {} skipped", skipped)?; }
+	writeln!(out)?;
+	Ok(failed == 0)
+}
+
+fn test<C: Cell>(
+	program: &Path,
+	new: &impl Fn() -> Lagoon<i32, C>,
+	chars: bool,
+	bless: bool,
+	out: &mut impl Write
+) -> io::Result<Verdict> {
+	let name = program.display();
+	let Files { source, output: expected_output, memory: expected_memory, input } = match load(program) {
+		Ok(files) => files,
+		Err(e) => return fail(program, e, out)
+	};
+	let expectations = match Expectations::<i32, C>::parse(&source) {
+		Ok(expectations) => expectations,
+		Err(e) => {
+			writeln!(out, "FAIL {}", name)?;
+			for d in e.diagnostics { writeln!(out, "    {}:{}", name, d)?; }
+			return Ok(Verdict::Fail);
+		}
+	};
+	let files = expected_output.is_some() || expected_memory.is_some();
+	if !files && expectations.is_empty() && !bless {
+		writeln!(out, "skip {} (no .out, .mem or //! directives)", name)?;
+		return Ok(Verdict::Skip);
+	}
+	let input = input.unwrap_or_else(|| expectations.input());
+	let outcome = execute(new, &name.to_string(), source, &input, &expectations, chars);
+
+	if let Some(error) = &outcome.error {
+		writeln!(out, "FAIL {}", name)?;
+		for line in error.lines() { writeln!(out, "    {}", line)?; }
+		return Ok(Verdict::Fail);
+	}
+	let mut blessed = false;
+	let (mut output, mut memory) = (None, None);
+	if bless {
+		let write = |path: PathBuf, contents: &str| fs::write(&path, contents).map_err(|e| (path, e));
+		if expected_output.is_some() || (!files && expectations.is_empty()) {
+			if let Err(e) = write(program.with_extension("out"), &outcome.output) { return fail(program, e, out); }
+			blessed = true;
+		}
+		if expected_memory.is_some() {
+			if let Err(e) = write(program.with_extension("mem"), &outcome.memory) { return fail(program, e, out); }
+			blessed = true;
+		}
+	} else {
+		output = expected_output.and_then(|expected| diff(&expected, &outcome.output));
+		memory = expected_memory.and_then(|expected| diff(&expected, &outcome.memory));
+	}
+	if output.is_none() && memory.is_none() && outcome.unmet.is_empty() {
+		if blessed {
+			writeln!(out, "bless {}", name)?;
+			return Ok(Verdict::Bless);
+		}
+		writeln!(out, "ok   {}", name)?;
+		return Ok(Verdict::Pass);
+	}
+	writeln!(out, "FAIL {}", name)?;
+	for (what, lines) in [("output", output), ("memory", memory)].iter() {
+		if let Some(lines) = lines {
+			writeln!(out, "  {}:", what)?;
+			for line in lines { writeln!(out, "    {}", line)?; }
+		}
+	}
+	for unmet in outcome.unmet.iter() { writeln!(out, "    {}", unmet)?; }
+	Ok(Verdict::Fail)
+}
+
+/// Fails the test of `program` because of the file that could not be read or
+/// written.
+fn fail(program: &Path, (path, e): FileError, out: &mut impl Write) -> io::Result<Verdict> {
+	writeln!(out, "FAIL {}", program.display())?;
+	writeln!(out, "    {}: {}", path.display(), e)?;
+	Ok(Verdict::Fail)
+}
+
+/// Reads `program` and the `.out`, `.mem` and `.in` files next to it.
+fn load(program: &Path) -> Result<Files, FileError> {
+	let next_to = |extension: &str| {
+		let path = program.with_extension(extension);
+		read(&path).map_err(|e| (path, e))
+	};
+	Ok(Files {
+		source: fs::read_to_string(program).map_err(|e| (program.to_path_buf(), e))?,
+		output: next_to("out")?,
+		memory: next_to("mem")?,
+		input: next_to("in")?
+	})
+}
+
+/// Runs `source` on a fresh `Lagoon`, reading from `input` and capturing what
+/// it writes, then checks it against `expectations`.
+fn execute<C: Cell>(
+	new: &impl Fn() -> Lagoon<i32, C>,
+	name: &str,
+	source: String,
+	input: &str,
+	expectations: &Expectations<i32, C>,
+	chars: bool
+) -> Outcome {
+	let mut lagoon = new();
+	let buffer = Buffer::new();
+	let (input_mode, output_mode) = if chars {
+		(InputMode::Character, OutputMode::Character)
+	} else {
+		(InputMode::Integer, OutputMode::Integer)
+	};
+	lagoon.pools.io = Io::new(
+		Input::new(io::Cursor::new(input.to_string())).with_mode(input_mode),
+		Output::new(buffer.clone()).with_mode(output_mode)
+	);
+	let error = match lagoon.append(source) {
+		Err(e) => Some(e.diagnostics.iter().map(|d| format!("{}:{}", name, d)).collect::<Vec<_>>().join("\n")),
+		Ok(()) => lagoon.run().err().map(|e| format!("{}:{}", name, e))
+	};
+	lagoon.pools.io.output.finish().ok();
+	let output = buffer.contents();
+	let unmet = expectations.check(&output, &lagoon.memory).iter().map(|d| format!("{}:{}", name, d)).collect();
+	Outcome { memory: memory(&lagoon), output, error, unmet }
+}
+
+/// The cells of `lagoon` that are not zero, in order.
+fn memory<C: Cell>(lagoon: &Lagoon<i32, C>) -> String {
+	let mut cells: Vec<_> = lagoon.memory.iter().filter(|(_, value)| !value.is_zero()).collect();
+	cells.sort_by_key(|(index, _)| **index);
+	cells.iter().map(|(index, value)| format!("{}: {}\n", index, value)).collect()
+}
+
+/// Every `.lag` file in `paths`, going into directories, in order, along with
+/// every directory that could not be read.
+fn discover(paths: &[String]) -> Vec<Result<PathBuf, FileError>> {
+	let mut found = Vec::new();
+	for path in paths {
+		walk(Path::new(path), &mut found);
+	}
+	found
+}
+
+fn walk(path: &Path, found: &mut Vec<Result<PathBuf, FileError>>) {
+	if !path.is_dir() {
+		found.push(Ok(path.to_path_buf()));
+		return;
+	}
+	let entries = fs::read_dir(path).and_then(|entries| entries.map(|e| e.map(|e| e.path())).collect());
+	let mut entries: Vec<PathBuf> = match entries {
+		Ok(entries) => entries,
+		Err(e) => {
+			found.push(Err((path.to_path_buf(), e)));
+			return;
+		}
+	};
+	entries.sort();
+	for entry in entries {
+		if entry.is_dir() || entry.extension().map_or(false, |e| e == "lag") {
+			walk(&entry, found);
+		}
+	}
+}
+
+/// The contents of the file at `path`, or `None` if there is no such file.
+fn read(path: &Path) -> io::Result<Option<String>> {
+	match fs::read_to_string(path) {
+		Ok(contents) => Ok(Some(contents)),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+		Err(e) => Err(e)
+	}
+}
+
+/// Every line of `expected` and `actual`, starting with `-` if it is only in
+/// `expected`, `+` if it is only in `actual` and a space if it is in both. If
+/// they have the same lines, there is no diff.
+fn diff(expected: &str, actual: &str) -> Option<Vec<String>> {
+	let a: Vec<&str> = expected.lines().collect();
+	let b: Vec<&str> = actual.lines().collect();
+	if a == b { return None; }
+	// The longest common subsequence of each pair of suffixes
+	let mut common = vec![vec![0; b.len() + 1]; a.len() + 1];
+	for i in (0..a.len()).rev() {
+		for j in (0..b.len()).rev() {
+			common[i][j] = if a[i] == b[j] {
+				common[i + 1][j + 1] + 1
+			} else {
+				common[i + 1][j].max(common[i][j + 1])
+			};
+		}
+	}
+	let mut lines = Vec::new();
+	let (mut i, mut j) = (0, 0);
+	while i < a.len() || j < b.len() {
+		if i < a.len() && j < b.len() && a[i] == b[j] {
+			lines.push(format!("  {}", a[i]));
+			i += 1;
+			j += 1;
+		} else if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+			lines.push(format!("- {}", a[i]));
+			i += 1;
+		} else {
+			lines.push(format!("+ {}", b[j]));
+			j += 1;
+		}
+	}
+	Some(lines)
+}
+
+#[cfg(test)]
+mod cases {
+	use std::{env, fs, process};
+	use std::path::{Path, PathBuf};
+	use crate::lagoon::{Lagoon, io::Buffer};
+	use super::{diff, run};
+
+	/// A fresh directory with `files` in it, for the test called `name`.
+	fn directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+		let dir = env::temp_dir().join(format!("lagoon-golden-{}-{}", process::id(), name));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		for (file, contents) in files { fs::write(dir.join(file), contents).unwrap(); }
+		dir
+	}
+
+	/// Runs the tests in `dir`, returning whether they passed and the report
+	/// with `dir` taken out.
+	fn report(dir: &Path, bless: bool) -> (bool, String) {
+		let new = || {
+			let mut lagoon: Lagoon<i32> = Lagoon::new();
+			lagoon.gen_pools();
+			lagoon.budget.steps = Some(100);
+			lagoon
+		};
+		let mut out = Buffer::new();
+		let passed = run(&[dir.display().to_string()], new, false, bless, &mut out).unwrap();
+		(passed, out.contents().replace(&format!("{}/", dir.display()), ""))
+	}
+
+	#[test]
+	fn passes() {
+		let dir = directory("passes", &[
+			("add.lag", "0i 1i; 0i 1i 0- 1+\n{0|;}\n0- 1+ 1o;"),
+			("add.in", "3 4"),
+			("add.out", "7\n"),
+			("add.mem", "1: 7\n"),
+			("notes.lag", "0+;")
+		]);
+		assert_eq!(report(&dir, false), (true, "ok   add.lag\nskip notes.lag (no .out, .mem or //! directives)\n1 passed, 0 failed, 1 skipped\n".to_string()));
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn fails() {
+		let dir = directory("fails", &[
+			("count.lag", "0+;;0o;"),
+			("count.out", "1\n"),
+			("count.mem", "0: 2\n"),
+			("spin.lag", "0+;\n{0|;}"),
+			("spin.out", "")
+		]);
+		let (passed, report) = report(&dir, false);
+		assert!(!passed);
+		assert_eq!(report, "\
+			FAIL count.lag\n  \
+			  output:\n    \
+			    - 1\n    \
+			    + 3\n  \
+			  memory:\n    \
+			    - 0: 2\n    \
+			    + 0: 3\n\
+			FAIL spin.lag\n    \
+			    spin.lag:2:4: exceeded the limit of 100 steps with memory [0: 100]\n\
+			0 passed, 2 failed\n"
+		);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn bless() {
+		let dir = directory("bless", &[
+			("count.lag", "0+;;0o;"),
+			("count.out", "1\n"),
+			("count.mem", ""),
+			("new.lag", "0+ 0o;")
+		]);
+		assert_eq!(report(&dir, true), (true, "bless count.lag\nbless new.lag\n0 passed, 0 failed, 2 blessed\n".to_string()));
+		assert_eq!(fs::read_to_string(dir.join("count.out")).unwrap(), "3\n");
+		assert_eq!(fs::read_to_string(dir.join("count.mem")).unwrap(), "0: 3\n");
+		assert_eq!(fs::read_to_string(dir.join("new.out")).unwrap(), "1\n");
+		assert!(!dir.join("new.mem").exists());
+		assert!(report(&dir, false).0);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn directives() {
+		let dir = directory("directives", &[
+			("broken.lag", "//! expect 0\n0+;"),
+			("double.lag", "//! input: 4\n//! output: 8\n//! expect 0 = 8\n0i; 0i 0*; 0* 0o;"),
+			("wrong.lag", "//! output: 2\n//! expect 0 = 2\n0+ 0o;")
+		]);
+		assert_eq!(report(&dir, false), (false, "\
+			FAIL broken.lag\n    \
+			    broken.lag:1:1: expected `expect <index> = <value>`, found `expect 0`\n\
+			ok   double.lag\n\
+			FAIL wrong.lag\n    \
+			    wrong.lag:1:1: expected output `2`, found `1`\n    \
+			    wrong.lag:2:1: expected cell 0 to hold 2, found 1\n\
+			1 passed, 2 failed\n".to_string()
+		));
+		// Blessing leaves programs that only have directives alone
+		assert!(!report(&dir, true).0);
+		assert!(!dir.join("double.out").exists());
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn unreadable() {
+		let dir = directory("unreadable", &[
+			("add.lag", "//! expect 0 = 1\n0+;"),
+			("binary.lag", "")
+		]);
+		fs::write(dir.join("binary.lag"), [0xff, 0xfe]).unwrap();
+		let missing = dir.join("missing.lag").display().to_string();
+		let mut out = Buffer::new();
+		let new = || {
+			let mut lagoon: Lagoon<i32> = Lagoon::new();
+			lagoon.gen_pools();
+			lagoon
+		};
+		assert!(!run(&[missing, dir.display().to_string()], new, false, false, &mut out).unwrap());
+		let report = out.contents().replace(&format!("{}/", dir.display()), "");
+		let lines: Vec<&str> = report.lines().collect();
+		assert_eq!(lines[0], "FAIL missing.lag");
+		assert!(lines[1].starts_with("    missing.lag: "), "{}", lines[1]);
+		assert_eq!(lines[2], "ok   add.lag");
+		assert_eq!(lines[3], "FAIL binary.lag");
+		assert!(lines[4].starts_with("    binary.lag: "), "{}", lines[4]);
+		assert_eq!(lines[5], "1 passed, 2 failed");
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn diffs() {
+		assert_eq!(diff("1\n2\n3\n", "1\n2\n3"), None);
+		assert_eq!(diff("1\n2\n3\n", "1\n4\n3\n5\n"), Some(vec![
+			"  1".to_string(),
+			"- 2".to_string(),
+			"+ 4".to_string(),
+			"  3".to_string(),
+			"+ 5".to_string()
+		]));
+	}
+}
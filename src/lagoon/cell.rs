@@ -0,0 +1,181 @@
+use std::{convert::TryFrom, fmt, ops::AddAssign, str::FromStr};
+
+/// What happens when arithmetic on a cell goes past what it can hold.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Overflow {
+	/// Stop with a runtime error
+	#[default]
+	Check,
+	/// Wrap around to the other end
+	Wrap
+}
+
+/// The cell types a front end can choose between. A `Lagoon` is built for one
+/// of them through its `C` parameter.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum CellType {
+	U8,
+	#[default]
+	I32,
+	I64,
+	#[cfg(feature = "bigint")]
+	BigInt
+}
+
+impl FromStr for CellType {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, String> {
+		match text {
+			"u8" => Ok(CellType::U8),
+			"i32" => Ok(CellType::I32),
+			"i64" => Ok(CellType::I64),
+			#[cfg(feature = "bigint")]
+			"bigint" => Ok(CellType::BigInt),
+			_ => Err(format!("unknown cell type `{}`", text))
+		}
+	}
+}
+
+/// A value memory can hold. Every operation that could go past what the cell
+/// holds takes the `Overflow` to follow, and returns `None` if that means
+/// failing.
+pub trait Cell:
+	Clone + PartialEq + PartialOrd + Default + fmt::Display + fmt::Debug + FromStr + 'static
+{
+	const TYPE: CellType;
+	/// A change to a cell, wide enough that adding up what every pool does to
+	/// it in one `;` cannot overflow.
+	type Delta: Clone + Default + AddAssign;
+
+	fn from_i64(n: i64, overflow: Overflow) -> Option<Self>;
+	/// The value as an `i32`, for things like code points, if it fits.
+	fn to_i32(&self) -> Option<i32>;
+	fn to_i64(&self) -> Option<i64>;
+	fn add(&self, other: &Self, overflow: Overflow) -> Option<Self>;
+	fn sub(&self, other: &Self, overflow: Overflow) -> Option<Self>;
+	fn mul(&self, other: &Self, overflow: Overflow) -> Option<Self>;
+	fn neg(&self, overflow: Overflow) -> Option<Self>;
+	/// How far `to` is from `self`.
+	fn change(&self, to: &Self) -> Self::Delta;
+	/// The cell moved by `delta`, which is only checked against what it can
+	/// hold at the end.
+	fn shift(&self, delta: &Self::Delta, overflow: Overflow) -> Option<Self>;
+
+	fn is_zero(&self) -> bool { *self == Self::default() }
+}
+
+macro_rules! primitive {
+	($($t:ty => $type:ident),*) => {$(
+		impl Cell for $t {
+			const TYPE: CellType = CellType::$type;
+			type Delta = i128;
+
+			fn from_i64(n: i64, overflow: Overflow) -> Option<Self> {
+				match overflow {
+					Overflow::Check => <$t>::try_from(n).ok(),
+					Overflow::Wrap => Some(n as $t)
+				}
+			}
+
+			fn to_i32(&self) -> Option<i32> { i32::try_from(*self).ok() }
+			fn to_i64(&self) -> Option<i64> { i64::try_from(*self).ok() }
+
+			fn add(&self, other: &Self, overflow: Overflow) -> Option<Self> {
+				match overflow {
+					Overflow::Check => self.checked_add(*other),
+					Overflow::Wrap => Some(self.wrapping_add(*other))
+				}
+			}
+
+			fn sub(&self, other: &Self, overflow: Overflow) -> Option<Self> {
+				match overflow {
+					Overflow::Check => self.checked_sub(*other),
+					Overflow::Wrap => Some(self.wrapping_sub(*other))
+				}
+			}
+
+			fn mul(&self, other: &Self, overflow: Overflow) -> Option<Self> {
+				match overflow {
+					Overflow::Check => self.checked_mul(*other),
+					Overflow::Wrap => Some(self.wrapping_mul(*other))
+				}
+			}
+
+			fn neg(&self, overflow: Overflow) -> Option<Self> {
+				match overflow {
+					Overflow::Check => self.checked_neg(),
+					Overflow::Wrap => Some(self.wrapping_neg())
+				}
+			}
+
+			fn change(&self, to: &Self) -> i128 { *to as i128 - *self as i128 }
+
+			fn shift(&self, delta: &i128, overflow: Overflow) -> Option<Self> {
+				match overflow {
+					Overflow::Check => (*self as i128).checked_add(*delta).and_then(|n| <$t>::try_from(n).ok()),
+					Overflow::Wrap => Some((*self as i128).wrapping_add(*delta) as $t)
+				}
+			}
+		}
+	)*};
+}
+
+primitive!(u8 => U8, i32 => I32, i64 => I64);
+
+/// Unbounded, so it never overflows whatever `Overflow` says.
+#[cfg(feature = "bigint")]
+impl Cell for num_bigint::BigInt {
+	const TYPE: CellType = CellType::BigInt;
+	type Delta = Self;
+
+	fn from_i64(n: i64, _: Overflow) -> Option<Self> { Some(n.into()) }
+	fn to_i32(&self) -> Option<i32> { i32::try_from(self).ok() }
+	fn to_i64(&self) -> Option<i64> { i64::try_from(self).ok() }
+	fn add(&self, other: &Self, _: Overflow) -> Option<Self> { Some(self + other) }
+	fn sub(&self, other: &Self, _: Overflow) -> Option<Self> { Some(self - other) }
+	fn mul(&self, other: &Self, _: Overflow) -> Option<Self> { Some(self * other) }
+	fn neg(&self, _: Overflow) -> Option<Self> { Some(-self) }
+	fn change(&self, to: &Self) -> Self { to - self }
+	fn shift(&self, delta: &Self, _: Overflow) -> Option<Self> { Some(self + delta) }
+}
+
+#[cfg(test)]
+mod arithmetic {
+	use super::{Cell, Overflow};
+
+	#[test]
+	fn checked() {
+		assert_eq!(i32::MAX.add(&1, Overflow::Check), None);
+		assert_eq!(0u8.sub(&1, Overflow::Check), None);
+		assert_eq!(3i64.mul(&-4, Overflow::Check), Some(-12));
+		assert_eq!(u8::from_i64(300, Overflow::Check), None);
+	}
+
+	#[test]
+	fn wrapping() {
+		assert_eq!(255u8.add(&1, Overflow::Wrap), Some(0));
+		assert_eq!(0u8.sub(&1, Overflow::Wrap), Some(255));
+		assert_eq!(1u8.neg(Overflow::Wrap), Some(255));
+		assert_eq!(u8::from_i64(-1, Overflow::Wrap), Some(255));
+	}
+
+	#[test]
+	fn deltas() {
+		let mut delta = 0i32.change(&2_000_000_000);
+		delta += 0i32.change(&-2_000_000_000);
+		assert_eq!(0i32.shift(&delta, Overflow::Check), Some(0));
+		assert_eq!(i32::MAX.shift(&1, Overflow::Check), None);
+		assert_eq!(255u8.shift(&2, Overflow::Wrap), Some(1));
+	}
+
+	#[cfg(feature = "bigint")]
+	#[test]
+	fn unbounded() {
+		use num_bigint::BigInt;
+		let big = BigInt::from(i64::MAX);
+		let doubled = big.mul(&BigInt::from(2), Overflow::Check).unwrap();
+		assert_eq!(doubled.to_string(), "18446744073709551614");
+		assert_eq!(doubled.to_i32(), None);
+	}
+}
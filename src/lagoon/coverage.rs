@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use super::executor::observer::Observer;
+use super::interpreter::expression::{Closure, Expression};
+use super::span::Span;
+
+/// How many times the guard of a loop held, and how many times it did not.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Branches {
+	pub held: usize,
+	pub failed: usize
+}
+
+impl Branches {
+	/// Whether the guard went both ways.
+	pub fn covered(&self) -> bool { self.held > 0 && self.failed > 0 }
+}
+
+/// An `Observer` that records which parts of some code ran. It can be kept
+/// across several runs of the same code to add them up.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Coverage {
+	/// How many times each toggle, `;` and loop ran, by where it is. A loop
+	/// runs once each time it is reached, however many times it goes around.
+	pub hits: BTreeMap<Span, usize>,
+	/// Which way the guard of each loop went
+	pub guards: BTreeMap<Span, Branches>
+}
+
+impl Coverage {
+	/// Coverage of `code`, with nothing run yet.
+	pub fn new<T: Eq + Hash + Clone>(code: &Closure<T>) -> Self {
+		let mut coverage = Self::default();
+		coverage.add(code);
+		coverage
+	}
+
+	fn add<T: Eq + Hash + Clone>(&mut self, closure: &Closure<T>) {
+		for e in closure.parts.iter() {
+			self.hits.insert(e.span(), 0);
+			if let Expression::Loop(l) = e {
+				self.guards.insert(l.span, Branches::default());
+				self.add(&l.closure);
+			}
+		}
+	}
+
+	/// Hits on each line, counting every expression that starts on it.
+	fn lines(&self) -> BTreeMap<i32, (usize, bool)> {
+		let mut lines: BTreeMap<i32, (usize, bool)> = BTreeMap::new();
+		for (span, hits) in self.hits.iter() {
+			let line = lines.entry(span.line).or_insert((0, true));
+			line.0 += hits;
+			line.1 &= *hits > 0;
+		}
+		lines
+	}
+
+	/// The coverage as an lcov tracefile for the source at `path`, with a line
+	/// for every line that has code and a pair of branches for every guard.
+	pub fn lcov(&self, path: &str) -> String {
+		let mut out = format!("TN:\nSF:{}\n", path);
+		for (n, (span, branches)) in self.guards.iter().enumerate() {
+			let reached = self.hits.get(span).map_or(false, |h| *h > 0);
+			let taken = |n: usize| if reached { n.to_string() } else { "-".to_string() };
+			out.push_str(&format!("BRDA:{},{},0,{}\n", span.line, n, taken(branches.held)));
+			out.push_str(&format!("BRDA:{},{},1,{}\n", span.line, n, taken(branches.failed)));
+		}
+		let branches_hit: usize = self.guards.values().map(|b| (b.held > 0) as usize + (b.failed > 0) as usize).sum();
+		out.push_str(&format!("BRF:{}\nBRH:{}\n", self.guards.len() * 2, branches_hit));
+		let lines = self.lines();
+		for (line, (hits, _)) in lines.iter() {
+			out.push_str(&format!("DA:{},{}\n", line, hits));
+		}
+		let lines_hit = lines.values().filter(|(hits, _)| *hits > 0).count();
+		out.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), lines_hit));
+		out
+	}
+
+	/// `source` with the hits on each line beside it, or `#####` for a line
+	/// with code where nothing ran. After it comes everything that never ran,
+	/// and every guard that did not go both ways.
+	pub fn listing(&self, source: &str) -> String {
+		let lines = self.lines();
+		let mut out = String::new();
+		for (n, line) in source.lines().enumerate() {
+			let hits = match lines.get(&(n as i32 + 1)) {
+				Some((0, _)) => "#####".to_string(),
+				Some((hits, true)) => hits.to_string(),
+				Some((hits, false)) => format!("{}*", hits),
+				None => String::new()
+			};
+			out.push_str(&format!("{:>10} | {}\n", hits, line));
+		}
+		for (span, _) in self.hits.iter().filter(|(_, hits)| **hits == 0) {
+			out.push_str(&format!("never ran: `{}` at {}\n", excerpt(span, source), span));
+		}
+		for (span, branches) in self.guards.iter().filter(|(_, b)| !b.covered()) {
+			let way = if branches.held == 0 { "held" } else { "failed" };
+			out.push_str(&format!("guard never {}: loop at {}\n", way, span));
+		}
+		let ran = self.hits.values().filter(|h| **h > 0).count();
+		let both = self.guards.values().filter(|b| b.covered()).count();
+		out.push_str(&format!(
+			"{} of {} expressions ran, {} of {} guards went both ways\n",
+			ran, self.hits.len(), both, self.guards.len()
+		));
+		out
+	}
+}
+
+/// The start of the code at `span`, up to the end of its first line.
+fn excerpt<'a>(span: &Span, source: &'a str) -> &'a str {
+	let text = source.get(span.start..span.end).unwrap_or("");
+	text.lines().next().unwrap_or("")
+}
+
+impl<T, C> Observer<T, C> for Coverage {
+	const UPDATES: bool = false;
+
+	fn toggle(&mut self, _pool: char, _index: &T, span: Span) { *self.hits.entry(span).or_insert(0) += 1; }
+
+	fn exec(&mut self, span: Span) { *self.hits.entry(span).or_insert(0) += 1; }
+
+	fn enter(&mut self, span: Span) { *self.hits.entry(span).or_insert(0) += 1; }
+
+	fn guard(&mut self, span: Span, holds: bool) {
+		let branches = self.guards.entry(span).or_default();
+		if holds { branches.held += 1; } else { branches.failed += 1; }
+	}
+}
+
+#[cfg(test)]
+mod reports {
+	use crate::lagoon::Lagoon;
+	use crate::lagoon::io::Io;
+	use super::{Branches, Coverage};
+
+	/// Counts down from 1, then skips a loop whose guard never holds.
+	const SOURCE: &str = "0+;0- 0+\n{0|;}\n{0|\n1+;\n}\n";
+
+	fn lagoon(source: &str) -> Lagoon<i32> {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.pools.io = Io::memory("").0;
+		lagoon.append_raw(source).unwrap();
+		lagoon
+	}
+
+	fn cover(source: &str) -> Coverage {
+		let mut lagoon = lagoon(source);
+		let mut coverage = Coverage::new(&lagoon.code);
+		lagoon.run_observed(&mut coverage).unwrap();
+		coverage
+	}
+
+	#[test]
+	fn nodes() {
+		let coverage = cover(SOURCE);
+		let never: Vec<i32> = coverage.hits.iter().filter(|(_, h)| **h == 0).map(|(s, _)| s.line).collect();
+		assert_eq!(never, vec![4, 4]);
+		assert_eq!(coverage.hits.len(), 9);
+	}
+
+	#[test]
+	fn guards() {
+		let coverage = cover(SOURCE);
+		let guards: Vec<Branches> = coverage.guards.values().cloned().collect();
+		assert_eq!(guards, vec![Branches { held: 1, failed: 1 }, Branches { held: 0, failed: 1 }]);
+		assert!(guards[0].covered());
+		assert!(!guards[1].covered());
+	}
+
+	#[test]
+	fn lcov() {
+		assert_eq!(cover(SOURCE).lcov("count.lag"), "\
+			TN:\n\
+			SF:count.lag\n\
+			BRDA:2,0,0,1\n\
+			BRDA:2,0,1,1\n\
+			BRDA:3,1,0,0\n\
+			BRDA:3,1,1,1\n\
+			BRF:4\n\
+			BRH:3\n\
+			DA:1,4\n\
+			DA:2,2\n\
+			DA:3,1\n\
+			DA:4,0\n\
+			LF:4\n\
+			LH:3\n\
+			end_of_record\n"
+		);
+	}
+
+	#[test]
+	fn listing() {
+		assert_eq!(cover(SOURCE).listing(SOURCE), "\
+			\x20        4 | 0+;0- 0+\n\
+			\x20        2 | {0|;}\n\
+			\x20        1 | {0|\n\
+			\x20    ##### | 1+;\n\
+			\x20          | }\n\
+			never ran: `1+` at 4:1\n\
+			never ran: `;` at 4:3\n\
+			guard never held: loop at 3:1\n\
+			7 of 9 expressions ran, 1 of 2 guards went both ways\n"
+		);
+	}
+
+	#[test]
+	fn adds_up() {
+		let mut coverage = cover(SOURCE);
+		lagoon(SOURCE).run_observed(&mut coverage).unwrap();
+		assert_eq!(coverage, {
+			let mut twice = cover(SOURCE);
+			for h in twice.hits.values_mut() { *h *= 2; }
+			for b in twice.guards.values_mut() { *b = Branches { held: b.held * 2, failed: b.failed * 2 }; }
+			twice
+		});
+	}
+}
@@ -0,0 +1,304 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::{Lagoon, cell::Cell, index::Index, span::Span};
+use super::executor::{Executor, budget::Meter, error::RuntimeError};
+use super::interpreter::expression::{Closure, Expression, factory::new_closure};
+
+/// What a single `Debugger::step` did.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Step<T> {
+	Toggle { pool: char, index: T, span: Span },
+	Exec(Span),
+	/// Checked the guard of the loop at `span`, going into its body if it held
+	Guard { span: Span, holds: bool },
+	/// There was nothing left to run
+	Done
+}
+
+/// Something to stop at when it changes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Watch<T> {
+	/// The value of a cell
+	Cell(T),
+	/// The indices in a pool
+	Pool(char)
+}
+
+/// Why `Debugger::resume` stopped.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Stop<T> {
+	/// The next expression is on a line with a breakpoint
+	Breakpoint(Span),
+	/// The last step changed what is being watched
+	Watchpoint(Watch<T>),
+	/// The code ran to the end
+	Finished
+}
+
+/// What a watched thing looked like before a step. A cell that was never
+/// touched holds the default value, so touching it changes nothing.
+#[derive(PartialEq)]
+enum Seen<T, C> {
+	Cell(C),
+	Pool(Option<BTreeSet<T>>)
+}
+
+/// Runs the code of a `Lagoon` one expression at a time, so that its memory
+/// and pools can be looked at in between. A loop takes a step to check its
+/// guard each time around, on top of the steps its body takes.
+pub struct Debugger<'a, T: Index, C = i32> {
+	lagoon: &'a mut Lagoon<T, C>,
+	/// Where each closure that is running is up to, outermost first. Every
+	/// closure but the last is at the loop it is running the body of.
+	stack: Vec<usize>,
+	/// Whether anything has been stepped through yet
+	started: bool,
+	meter: Meter,
+	/// Lines to stop before running anything on
+	pub breakpoints: BTreeSet<i32>,
+	pub watchpoints: Vec<Watch<T>>
+}
+
+impl<'a, T: Index, C: Cell> Debugger<'a, T, C> {
+	/// Starts at the beginning of `lagoon.code`, with the clock on its budget
+	/// running from now.
+	pub fn new(lagoon: &'a mut Lagoon<T, C>) -> Self {
+		let meter = Meter::new(lagoon.budget);
+		Self {
+			lagoon,
+			stack: vec![0],
+			started: false,
+			meter,
+			breakpoints: BTreeSet::new(),
+			watchpoints: Vec::new()
+		}
+	}
+
+	pub fn lagoon(&self) -> &Lagoon<T, C> { self.lagoon }
+
+	pub fn memory(&self) -> &HashMap<T, C> { &self.lagoon.memory }
+
+	/// The indices in pool `c`.
+	pub fn pool(&self, c: char) -> Option<&BTreeSet<T>> {
+		self.lagoon.pools.map.get(&c).map(|p| &p.set)
+	}
+
+	/// How many loops deep the next expression is.
+	pub fn depth(&self) -> usize { self.stack.len() - 1 }
+
+	/// The expression the next step runs, or `None` once there is none left.
+	pub fn next(&self) -> Option<&Expression<T>> {
+		// Past the end of a body, its loop is next
+		(1..=self.stack.len()).rev().find_map(|depth| self.at(&self.stack[..depth]))
+	}
+
+	pub fn break_at(&mut self, line: i32) -> &mut Self {
+		self.breakpoints.insert(line);
+		return self;
+	}
+
+	pub fn watch(&mut self, watch: Watch<T>) -> &mut Self {
+		self.watchpoints.push(watch);
+		return self;
+	}
+
+	/// Runs the next toggle, `;` or guard check. If it fails, the debugger
+	/// stays where it was.
+	pub fn step(&mut self) -> Result<Step<T>, RuntimeError<T>> {
+		self.started = true;
+		// A finished body hands back to its loop, whose guard is checked next
+		while self.stack.len() > 1 && self.at(&self.stack).is_none() {
+			self.stack.pop();
+		}
+		let step = match self.at(&self.stack) {
+			None => return Ok(Step::Done),
+			Some(Expression::Loop(l)) => {
+				let memory = &self.lagoon.memory;
+				let holds = l.guard.iter().all(|g| {
+					let value = memory.get(&g.index).map_or(false, |v| !v.is_zero());
+					value != g.negated
+				});
+				Step::Guard { span: l.span, holds }
+			},
+			Some(e) => {
+				let e = e.clone();
+				self.run(new_closure(vec![e.clone()]))?;
+				match e {
+					Expression::Instruction(i) => Step::Toggle { pool: i.pool, index: i.index, span: i.span },
+					e => Step::Exec(e.span())
+				}
+			}
+		};
+		match step {
+			Step::Guard { holds: true, span } => {
+				self.meter.iteration()
+					.map_err(|limit| RuntimeError::out_of_budget(limit, span, self.lagoon.memory.iter()))?;
+				self.stack.push(0);
+			},
+			_ => *self.stack.last_mut().unwrap() += 1
+		}
+		Ok(step)
+	}
+
+	/// Steps until a watched thing changes, the next expression is on a line
+	/// with a breakpoint, or the code runs out. Before anything has run, it
+	/// first stops at a breakpoint on the first expression. Otherwise it takes
+	/// at least one step, so it can be called again to get past a breakpoint.
+	pub fn resume(&mut self) -> Result<Stop<T>, RuntimeError<T>> {
+		if !self.started {
+			self.started = true;
+			if let Some(e) = self.next() {
+				if self.breakpoints.contains(&e.span().line) { return Ok(Stop::Breakpoint(e.span())); }
+			}
+		}
+		loop {
+			let before: Vec<Seen<T, C>> = self.watchpoints.iter().map(|w| self.seen(w)).collect();
+			if self.step()? == Step::Done { return Ok(Stop::Finished); }
+			let changed = self.watchpoints.iter().zip(before).find(|(w, seen)| self.seen(w) != *seen);
+			if let Some((watch, _)) = changed { return Ok(Stop::Watchpoint(watch.clone())); }
+			if let Some(e) = self.next() {
+				if self.breakpoints.contains(&e.span().line) { return Ok(Stop::Breakpoint(e.span())); }
+			}
+		}
+	}
+
+	/// The expression at `path`, which is laid out like `stack`.
+	fn at(&self, path: &[usize]) -> Option<&Expression<T>> {
+		let (last, loops) = path.split_last()?;
+		let mut closure = &self.lagoon.code;
+		for at in loops {
+			closure = match closure.parts.get(*at) {
+				Some(Expression::Loop(l)) => &l.closure,
+				_ => return None
+			};
+		}
+		closure.parts.get(*last)
+	}
+
+	fn seen(&self, watch: &Watch<T>) -> Seen<T, C> {
+		match watch {
+			Watch::Cell(index) => Seen::Cell(self.lagoon.memory.get(index).cloned().unwrap_or_default()),
+			Watch::Pool(c) => Seen::Pool(self.pool(*c).cloned())
+		}
+	}
+
+	/// Runs `closure` on an `Executor` that picks up the meter where the last
+	/// one left it.
+	fn run(&mut self, closure: Closure<T>) -> Result<(), RuntimeError<T>> {
+		let lagoon = &mut *self.lagoon;
+		let mut executor = Executor::new(&mut lagoon.memory, &mut lagoon.pools)
+			.with_semantics(lagoon.semantics)
+			.with_overflow(lagoon.config.overflow);
+		executor.meter = self.meter.clone();
+		let result = executor.run(&closure);
+		self.meter = executor.meter;
+		result
+	}
+}
+
+#[cfg(test)]
+mod stepping {
+	use crate::lagoon::Lagoon;
+	use crate::lagoon::interpreter::expression::Expression;
+	use crate::lagoon::io::Io;
+	use crate::lagoon::span::Span;
+	use super::{Debugger, Step, Stop, Watch};
+
+	fn lagoon(source: &str) -> Lagoon<i32> {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.pools.io = Io::memory("").0;
+		lagoon.append_raw(source).unwrap();
+		lagoon
+	}
+
+	#[test]
+	fn one_at_a_time() {
+		let mut lagoon = lagoon("0+;0+ 0-\n{0|;}");
+		let mut debugger = Debugger::new(&mut lagoon);
+		let mut steps = Vec::new();
+		loop {
+			let step = debugger.step().unwrap();
+			if step == Step::Done { break; }
+			steps.push(step);
+		}
+		let guard = Span::new(2, 1, 9, 14);
+		let exec = Span::new(2, 4, 12, 13);
+		assert_eq!(steps, vec![
+			Step::Toggle { pool: '+', index: 0, span: Span::new(1, 1, 0, 2) },
+			Step::Exec(Span::new(1, 3, 2, 3)),
+			Step::Toggle { pool: '+', index: 0, span: Span::new(1, 4, 3, 5) },
+			Step::Toggle { pool: '-', index: 0, span: Span::new(1, 7, 6, 8) },
+			Step::Guard { span: guard, holds: true },
+			Step::Exec(exec),
+			Step::Guard { span: guard, holds: false }
+		]);
+		assert_eq!(debugger.memory()[&0], 0);
+		assert_eq!(debugger.step(), Ok(Step::Done));
+	}
+
+	#[test]
+	fn inspect() {
+		let mut lagoon = lagoon("0+ 1o; 1+ {!1|;}");
+		let mut debugger = Debugger::new(&mut lagoon);
+		for _ in 0..4 { debugger.step().unwrap(); }
+		assert!(debugger.pool('+').unwrap().iter().eq(&[0, 1]));
+		assert!(debugger.pool('o').unwrap().iter().eq(&[1]));
+		assert_eq!(debugger.memory().get(&0), Some(&1));
+		assert!(matches!(debugger.next(), Some(Expression::Loop(_))));
+		debugger.step().unwrap();
+		assert_eq!(debugger.depth(), 1);
+	}
+
+	#[test]
+	fn breakpoints() {
+		let mut lagoon = lagoon("0+;;\n0+ 0-\n{0|\n;\n}\n0o;");
+		let mut debugger = Debugger::new(&mut lagoon);
+		debugger.break_at(4).break_at(6);
+		assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(Span::new(4, 1, 15, 16))));
+		assert_eq!(debugger.memory()[&0], 2);
+		assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(Span::new(4, 1, 15, 16))));
+		assert_eq!(debugger.memory()[&0], 1);
+		assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(Span::new(6, 1, 19, 21))));
+		assert_eq!(debugger.memory()[&0], 0);
+		assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(Span::new(6, 3, 21, 22))));
+		assert_eq!(debugger.resume(), Ok(Stop::Finished));
+	}
+
+	#[test]
+	fn first_line() {
+		let mut lagoon = lagoon("0+;\n0o;");
+		let mut debugger = Debugger::new(&mut lagoon);
+		debugger.break_at(1);
+		assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(Span::new(1, 1, 0, 2))));
+		assert!(debugger.memory().is_empty());
+		assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(Span::new(1, 3, 2, 3))));
+		assert_eq!(debugger.resume(), Ok(Stop::Finished));
+		assert_eq!(debugger.memory()[&0], 2);
+	}
+
+	#[test]
+	fn watchpoints() {
+		let mut lagoon = lagoon("0+ 1+; 1+ 1-; 0+ 1- {1|;}");
+		let mut debugger = Debugger::new(&mut lagoon);
+		debugger.watch(Watch::Cell(1)).watch(Watch::Pool('-'));
+		assert_eq!(debugger.resume(), Ok(Stop::Watchpoint(Watch::Cell(1))));
+		assert_eq!(debugger.memory()[&1], 1);
+		assert_eq!(debugger.resume(), Ok(Stop::Watchpoint(Watch::Pool('-'))));
+		assert_eq!(debugger.resume(), Ok(Stop::Watchpoint(Watch::Cell(1))));
+		assert_eq!(debugger.memory()[&1], 0);
+		assert_eq!(debugger.resume(), Ok(Stop::Watchpoint(Watch::Pool('-'))));
+		assert!(debugger.pool('-').unwrap().is_empty());
+		assert_eq!(debugger.resume(), Ok(Stop::Finished));
+	}
+
+	#[test]
+	fn unchanged() {
+		let mut lagoon = lagoon("1o; 1o 1+;");
+		let mut debugger = Debugger::new(&mut lagoon);
+		debugger.watch(Watch::Cell(1));
+		assert_eq!(debugger.resume(), Ok(Stop::Watchpoint(Watch::Cell(1))));
+		assert_eq!(debugger.memory()[&1], 1);
+		assert_eq!(debugger.resume(), Ok(Stop::Finished));
+	}
+}
@@ -0,0 +1,31 @@
+use std::{error, fmt};
+
+use super::executor::error::RuntimeError;
+use super::parser::error::ParseError;
+
+/// Anything that can go wrong between handing Lagoon some source and it
+/// finishing running.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error<T> {
+	Parse(ParseError),
+	Runtime(RuntimeError<T>)
+}
+
+impl<T> From<ParseError> for Error<T> {
+	fn from(e: ParseError) -> Self { Error::Parse(e) }
+}
+
+impl<T> From<RuntimeError<T>> for Error<T> {
+	fn from(e: RuntimeError<T>) -> Self { Error::Runtime(e) }
+}
+
+impl<T: fmt::Display> fmt::Display for Error<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::Parse(e) => write!(f, "{}", e),
+			Error::Runtime(e) => write!(f, "{}", e)
+		}
+	}
+}
+
+impl<T: fmt::Debug + fmt::Display> error::Error for Error<T> {}
@@ -0,0 +1,99 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How much a single run may do before it is stopped. Nothing is limited by
+/// default.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Budget {
+	/// The most `;` to execute
+	pub steps: Option<usize>,
+	/// The most toggles to make
+	pub instructions: Option<usize>,
+	/// The most times to go around a loop, counting every loop together
+	pub iterations: Option<usize>,
+	/// How long the run may take
+	pub time: Option<Duration>
+}
+
+/// The part of a `Budget` that ran out.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Limit {
+	Steps(usize),
+	Instructions(usize),
+	Iterations(usize),
+	Time(Duration)
+}
+
+impl fmt::Display for Limit {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Limit::Steps(n) => write!(f, "the limit of {} steps", n),
+			Limit::Instructions(n) => write!(f, "the limit of {} instructions", n),
+			Limit::Iterations(n) => write!(f, "the limit of {} loop iterations", n),
+			Limit::Time(t) => write!(f, "the time limit of {:?}", t)
+		}
+	}
+}
+
+/// Keeps count of what a run has used of its `Budget`. The clock starts when
+/// the meter is made.
+#[derive(Debug, Clone)]
+pub struct Meter {
+	pub budget: Budget,
+	pub steps: usize,
+	pub instructions: usize,
+	pub iterations: usize,
+	deadline: Option<Instant>
+}
+
+impl Meter {
+	pub fn new(budget: Budget) -> Self {
+		Self {
+			budget,
+			steps: 0,
+			instructions: 0,
+			iterations: 0,
+			deadline: budget.time.map(|t| Instant::now() + t)
+		}
+	}
+
+	/// Counts a `;`.
+	pub fn step(&mut self) -> Result<(), Limit> {
+		self.steps += 1;
+		check(self.steps, self.budget.steps, Limit::Steps)?;
+		self.clock()
+	}
+
+	/// Counts a toggle.
+	pub fn instruction(&mut self) -> Result<(), Limit> {
+		self.instructions += 1;
+		check(self.instructions, self.budget.instructions, Limit::Instructions)
+	}
+
+	/// Counts a trip around a loop.
+	pub fn iteration(&mut self) -> Result<(), Limit> {
+		self.iterations += 1;
+		check(self.iterations, self.budget.iterations, Limit::Iterations)?;
+		self.clock()
+	}
+
+	/// Whether `n` more trips around a loop of a single `;` fit in the budget.
+	pub fn allows(&self, n: usize) -> bool {
+		let fits = |used: usize, limit: Option<usize>| limit.map_or(true, |l| used.saturating_add(n) <= l);
+		fits(self.steps, self.budget.steps) && fits(self.iterations, self.budget.iterations)
+	}
+
+	fn clock(&self) -> Result<(), Limit> {
+		match (self.deadline, self.budget.time) {
+			(Some(deadline), Some(time)) if Instant::now() > deadline => Err(Limit::Time(time)),
+			_ => Ok(())
+		}
+	}
+}
+
+fn check(used: usize, limit: Option<usize>, to: fn(usize) -> Limit) -> Result<(), Limit> {
+	match limit {
+		Some(limit) if used > limit => Err(to(limit)),
+		_ => Ok(())
+	}
+}
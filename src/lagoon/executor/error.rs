@@ -0,0 +1,83 @@
+use std::{error::Error, fmt};
+
+use crate::lagoon::{pool::Fault, span::Span};
+use super::budget::Limit;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RuntimeError<T> {
+	/// An instruction toggled an index in a pool that does not exist
+	UnknownPool { pool: char, index: T, span: Span },
+	/// A pool pushed an index past what a cell can hold
+	Overflow { pool: char, index: T, span: Span },
+	/// A pool wanted input after all of it was read
+	InputExhausted { pool: char, index: T, span: Span },
+	/// A pool failed to read or write
+	Io { pool: char, index: T, span: Span, message: String },
+	/// A pool queued a membership edit naming a pool that does not exist
+	UnknownTarget { pool: char, target: char, index: T, span: Span },
+	/// The run used up part of its `Budget`. `memory` holds every cell that
+	/// had a value at that point, in index order and as it would be printed.
+	OutOfBudget { limit: Limit, span: Span, memory: Vec<(T, String)> }
+}
+
+impl<T> RuntimeError<T> {
+	pub fn from_fault(fault: Fault, pool: char, index: T, span: Span) -> Self {
+		match fault {
+			Fault::Overflow => RuntimeError::Overflow { pool, index, span },
+			Fault::InputExhausted => RuntimeError::InputExhausted { pool, index, span },
+			Fault::Io(message) => RuntimeError::Io { pool, index, span, message },
+			Fault::UnknownPool(target) => RuntimeError::UnknownTarget { pool, target, index, span }
+		}
+	}
+
+	/// Where in the source the error happened.
+	pub fn span(&self) -> Span {
+		match self {
+			RuntimeError::UnknownPool { span, .. } => *span,
+			RuntimeError::Overflow { span, .. } => *span,
+			RuntimeError::InputExhausted { span, .. } => *span,
+			RuntimeError::Io { span, .. } => *span,
+			RuntimeError::UnknownTarget { span, .. } => *span,
+			RuntimeError::OutOfBudget { span, .. } => *span
+		}
+	}
+}
+
+impl<T: Ord + Clone> RuntimeError<T> {
+	/// An `OutOfBudget` with a snapshot of `memory`.
+	pub fn out_of_budget<'a, C: fmt::Display + 'a>(
+		limit: Limit,
+		span: Span,
+		memory: impl Iterator<Item = (&'a T, &'a C)>
+	) -> Self where T: 'a {
+		let mut memory: Vec<(T, String)> = memory.map(|(i, v)| (i.clone(), v.to_string())).collect();
+		memory.sort_by(|a, b| a.0.cmp(&b.0));
+		RuntimeError::OutOfBudget { limit, span, memory }
+	}
+}
+
+impl<T: fmt::Display> fmt::Display for RuntimeError<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: ", self.span())?;
+		match self {
+			RuntimeError::UnknownPool { pool, index, .. } =>
+				write!(f, "cannot toggle `{}` in unknown pool `{}`", index, pool),
+			RuntimeError::Overflow { pool, index, .. } =>
+				write!(f, "pool `{}` overflowed index `{}`", pool, index),
+			RuntimeError::InputExhausted { pool, index, .. } =>
+				write!(f, "pool `{}` ran out of input for index `{}`", pool, index),
+			RuntimeError::Io { pool, index, message, .. } =>
+				write!(f, "pool `{}` failed on index `{}`: {}", pool, index, message),
+			RuntimeError::UnknownTarget { pool, target, index, .. } =>
+				write!(f, "pool `{}` on index `{}` edits unknown pool `{}`", pool, index, target),
+			RuntimeError::OutOfBudget { limit, memory, .. } => {
+				write!(f, "exceeded {}", limit)?;
+				let cells: Vec<String> = memory.iter().map(|(i, v)| format!("{}: {}", i, v)).collect();
+				if !cells.is_empty() { write!(f, " with memory [{}]", cells.join(", "))?; }
+				Ok(())
+			}
+		}
+	}
+}
+
+impl<T: fmt::Debug + fmt::Display> Error for RuntimeError<T> {}
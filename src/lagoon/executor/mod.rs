@@ -1,38 +1,230 @@
-use std::{collections::HashMap, hash::Hash};
+pub mod error;
+pub mod budget;
+pub mod observer;
 
-use super::pool;
+use std::collections::{BTreeMap, HashMap};
+
+use super::cell::{Cell, Overflow};
+use super::index::Index;
+use super::pool::{self, Context, Edit};
+use super::span::Span;
 use super::interpreter::expression::{Closure, Expression};
+use self::budget::{Budget, Limit, Meter};
+use self::error::RuntimeError;
+use self::observer::{Observer, Quiet};
+
+pub fn execute<T: Index, C: Cell>(
+	memory: &mut HashMap<T, C>,
+	pools: &mut pool::Pools<T, C>,
+	closure: &Closure<T>
+) -> Result<(), RuntimeError<T>> {
+	Executor::new(memory, pools).run(closure)
+}
+
+/// How the pools see each other's changes during a single `;`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Semantics {
+	/// Each pool runs on memory as the pools before it left it
+	#[default]
+	Sequential,
+	/// Every pool runs on memory as it was before the `;`, and the changes
+	/// they each made are added together afterwards
+	Simultaneous
+}
 
-pub fn execute<T: Eq + Hash + Copy>(
-	memory: &mut HashMap<T, i32>,
-	pools: &mut pool::Pools<T>,
-	closure: Closure<T>
-) {
-	for e in closure.parts {
-		match e {
-			Expression::Exec => {
-				for pool in pools.map.values() {
-					for index in pool.set.iter() {
-						(pool.func)((*memory).entry(*index).or_insert(0));
+/// Runs closures against `memory` and `pools`, keeping count of what it does
+/// in `meter` so that a run can be cut short by its `Budget`. On `;`, the
+/// pools run in `pools.order`, each over its indices in ascending order.
+/// Everything it does is reported to `observer` as it happens.
+pub struct Executor<'a, T: Index, C = i32, O = Quiet> {
+	pub memory: &'a mut HashMap<T, C>,
+	pub pools: &'a mut pool::Pools<T, C>,
+	pub semantics: Semantics,
+	pub overflow: Overflow,
+	pub meter: Meter,
+	pub observer: O
+}
+
+impl<'a, T: Index, C: Cell> Executor<'a, T, C> {
+	pub fn new(memory: &'a mut HashMap<T, C>, pools: &'a mut pool::Pools<T, C>) -> Self {
+		Self {
+			memory,
+			pools,
+			semantics: Semantics::Sequential,
+			overflow: Overflow::default(),
+			meter: Meter::new(Budget::default()),
+			observer: Quiet
+		}
+	}
+}
+
+impl<'a, T: Index, C: Cell, O: Observer<T, C>> Executor<'a, T, C, O> {
+	pub fn with_observer<P: Observer<T, C>>(self, observer: P) -> Executor<'a, T, C, P> {
+		Executor {
+			memory: self.memory,
+			pools: self.pools,
+			semantics: self.semantics,
+			overflow: self.overflow,
+			meter: self.meter,
+			observer
+		}
+	}
+
+	pub fn with_semantics(mut self, semantics: Semantics) -> Self {
+		self.semantics = semantics;
+		return self;
+	}
+
+	pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+		self.overflow = overflow;
+		return self;
+	}
+
+	/// Starts the clock on `budget`.
+	pub fn with_budget(mut self, budget: Budget) -> Self {
+		self.meter = Meter::new(budget);
+		return self;
+	}
+
+	/// Limits just the number of `;`.
+	pub fn with_step_limit(self, steps: Option<usize>) -> Self {
+		let budget = Budget { steps, ..self.meter.budget };
+		self.with_budget(budget)
+	}
+
+	pub fn run(&mut self, closure: &Closure<T>) -> Result<(), RuntimeError<T>> {
+		for e in closure.parts.iter() {
+			match e {
+				Expression::Exec(span) => self.exec(*span)?,
+				Expression::Repeat(n, span) => for _ in 0..*n { self.exec(*span)? },
+				Expression::Instruction(i) => {
+					self.meter.instruction().map_err(|limit| self.out_of_budget(limit, i.span))?;
+					match self.pools.get_pool(i.pool) {
+						Some(p) => p.toggle(i.index),
+						None => return Err(RuntimeError::UnknownPool {
+							pool: i.pool,
+							index: i.index,
+							span: i.span
+						})
+					};
+					self.observer.toggle(i.pool, &i.index, i.span);
+				},
+				Expression::Loop(l) => {
+					self.observer.enter(l.span);
+					loop {
+						let holds = l.guard.iter().all(|g| {
+							let value = self.memory.get(&g.index).map_or(false, |v| !v.is_zero());
+							if g.negated { !value } else { value }
+						});
+						self.observer.guard(l.span, holds);
+						if !holds { break; }
+						self.meter.iteration().map_err(|limit| self.out_of_budget(limit, l.span))?;
+						self.run(&l.closure)?;
 					}
+					self.observer.exit(l.span);
 				}
-			},
-			Expression::Instruction(i) => {
-				match pools.get_pool(i.pool) {
-					Ok(p) => p.toggle(i.index),
-					Err(s) => panic!("{}", s)
+			}
+		}
+		Ok(())
+	}
+
+	fn exec(&mut self, span: Span) -> Result<(), RuntimeError<T>> {
+		self.meter.step().map_err(|limit| self.out_of_budget(limit, span))?;
+		// Which pool and index queued each edit
+		let mut edits: Vec<(char, T, Edit)> = Vec::new();
+		let ran = match self.semantics {
+			Semantics::Sequential => self.exec_sequential(span, &mut edits),
+			Semantics::Simultaneous => self.exec_simultaneous(span, &mut edits)
+		};
+		if let Err(e) = ran.and_then(|()| self.edit(span, edits)) {
+			self.observer.fail(span);
+			return Err(e);
+		}
+		self.observer.exec(span);
+		Ok(())
+	}
+
+	/// Makes the edits the pools queued during the `;` at `span`.
+	fn edit(&mut self, span: Span, edits: Vec<(char, T, Edit)>) -> Result<(), RuntimeError<T>> {
+		for (id, index, edit) in edits {
+			self.pools.edit(edit).map_err(|target| RuntimeError::UnknownTarget {
+				pool: id,
+				target,
+				index,
+				span
+			})?;
+		}
+		Ok(())
+	}
+
+	fn out_of_budget(&self, limit: Limit, span: Span) -> RuntimeError<T> {
+		RuntimeError::out_of_budget(limit, span, self.memory.iter())
+	}
+
+	fn exec_sequential(
+		&mut self,
+		span: Span,
+		edits: &mut Vec<(char, T, Edit)>
+	) -> Result<(), RuntimeError<T>> {
+		let mut queued: Vec<Edit> = Vec::new();
+		for id in self.pools.order.iter() {
+			let pool = self.pools.map.get_mut(id).unwrap();
+			for index in pool.set.iter() {
+				let mut datum = self.memory.get(index).cloned().unwrap_or_default();
+				let before = if O::UPDATES { Some(datum.clone()) } else { None };
+				let mut context = Context {
+					index,
+					memory: self.memory,
+					io: &mut self.pools.io,
+					edits: &mut queued,
+					overflow: self.overflow
 				};
-				()
-			},
-			Expression::Loop(l) => {
-				while l.guard.iter().all(|g| {
-					let value = memory.get(&g.index).unwrap_or(&0) != &0;
-					if g.negated { !value } else { value }
-				}) {
-					execute(memory, pools, l.closure.clone())
-				}
+				let result = pool.func.apply(&mut datum, &mut context);
+				if let Some(before) = before { self.observer.update(*id, index, &before, &datum); }
+				self.memory.insert(*index, datum);
+				result.map_err(|f| RuntimeError::from_fault(f, *id, *index, span))?;
+				edits.extend(queued.drain(..).map(|edit| (*id, *index, edit)));
+			}
+		}
+		Ok(())
+	}
+
+	fn exec_simultaneous(
+		&mut self,
+		span: Span,
+		edits: &mut Vec<(char, T, Edit)>
+	) -> Result<(), RuntimeError<T>> {
+		let o = self.overflow;
+		let mut queued: Vec<Edit> = Vec::new();
+		// How far each index was moved, and the last pool that moved it, to
+		// blame if the total overflows. Only the total has to fit in a cell.
+		let mut deltas: BTreeMap<T, (C::Delta, char)> = BTreeMap::new();
+		for id in self.pools.order.iter() {
+			let pool = self.pools.map.get_mut(id).unwrap();
+			for index in pool.set.iter() {
+				let before = self.memory.get(index).cloned().unwrap_or_default();
+				let mut after = before.clone();
+				let mut context = Context {
+					index,
+					memory: self.memory,
+					io: &mut self.pools.io,
+					edits: &mut queued,
+					overflow: o
+				};
+				pool.func.apply(&mut after, &mut context)
+					.map_err(|f| RuntimeError::from_fault(f, *id, *index, span))?;
+				edits.extend(queued.drain(..).map(|edit| (*id, *index, edit)));
+				if O::UPDATES { self.observer.update(*id, index, &before, &after); }
+				let total = deltas.entry(*index).or_insert((C::Delta::default(), *id));
+				total.0 += before.change(&after);
+				total.1 = *id;
 			}
 		}
+		for (index, (delta, id)) in deltas {
+			let datum = self.memory.entry(index).or_default();
+			*datum = datum.shift(&delta, o).ok_or(RuntimeError::Overflow { pool: id, index, span })?;
+		}
+		Ok(())
 	}
 }
 
@@ -42,22 +234,24 @@ mod from_tree {
 
 	use crate::lagoon::interpreter::expression::factory as e;
 	use crate::lagoon::pool;
+	use crate::lagoon::span::Span;
 	use super::execute;
 
 	#[test]
 	fn simple() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
 		let mut pools: pool::Pools<i32> = pool::Pools::new();
-		pools.add_pool('+', |datum| *datum += 1);
-		pools.add_pool('-', |datum| *datum -= 1);
+		let s = Span::default();
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+		pools.add_pool('-', |datum, _| { *datum -= 1; Ok(()) });
 
 		let program = e::new_closure(vec![
-			e::new_instruction(0, '+'),
-			e::new_exec()
+			e::new_instruction(0, '+', s),
+			e::new_exec(s)
 		]);
 
 		assert_eq!(memory.get(&0).unwrap_or(&0), &0);
-		execute(&mut memory, &mut pools, program);
+		execute(&mut memory, &mut pools, &program).unwrap();
 		assert_eq!(memory.get(&0).unwrap_or(&0), &1);
 	}
 
@@ -65,19 +259,20 @@ mod from_tree {
 	fn toggle_index() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
 		let mut pools: pool::Pools<i32> = pool::Pools::new();
-		pools.add_pool('+', |datum| *datum += 1);
-		pools.add_pool('-', |datum| *datum -= 1);
+		let s = Span::default();
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+		pools.add_pool('-', |datum, _| { *datum -= 1; Ok(()) });
 
 		let program = e::new_closure(vec![
-			e::new_instruction(0, '+'),
-			e::new_instruction(0, '+'),
-			e::new_instruction(1, '+'),
-			e::new_exec()
+			e::new_instruction(0, '+', s),
+			e::new_instruction(0, '+', s),
+			e::new_instruction(1, '+', s),
+			e::new_exec(s)
 		]);
 
 		assert_eq!(memory.get(&0).unwrap_or(&0), &0);
 		assert_eq!(memory.get(&1).unwrap_or(&0), &0);
-		execute(&mut memory, &mut pools, program);
+		execute(&mut memory, &mut pools, &program).unwrap();
 		assert_eq!(memory.get(&0).unwrap_or(&0), &0);
 		assert_eq!(memory.get(&1).unwrap_or(&0), &1);
 	}
@@ -86,23 +281,24 @@ mod from_tree {
 	fn simple_loop() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
 		let mut pools: pool::Pools<i32> = pool::Pools::new();
-		pools.add_pool('+', |datum| *datum += 1);
-		pools.add_pool('-', |datum| *datum -= 1);
+		let s = Span::default();
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+		pools.add_pool('-', |datum, _| { *datum -= 1; Ok(()) });
 
 		let program = e::new_closure(vec![
-			e::new_instruction(0, '+'),
-			e::new_exec(),
-			e::new_exec(),
-			e::new_exec(),
-			e::new_instruction(0, '+'),
-			e::new_instruction(0, '-'),
-			e::new_instruction(1, '+'),
-			e::new_loop(vec![e::new_guard(0, false)], e::new_closure(vec![
-				e::new_exec()
-			]))
+			e::new_instruction(0, '+', s),
+			e::new_exec(s),
+			e::new_exec(s),
+			e::new_exec(s),
+			e::new_instruction(0, '+', s),
+			e::new_instruction(0, '-', s),
+			e::new_instruction(1, '+', s),
+			e::new_loop(vec![e::new_guard(0, false, s)], e::new_closure(vec![
+				e::new_exec(s)
+			]), s)
 		]);
 
-		execute(&mut memory, &mut pools, program);
+		execute(&mut memory, &mut pools, &program).unwrap();
 		assert_eq!(memory.get(&0).unwrap_or(&0), &0);
 		assert_eq!(memory.get(&1).unwrap_or(&0), &3);
 	}
@@ -111,26 +307,27 @@ mod from_tree {
 	fn negated_loop() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
 		let mut pools: pool::Pools<i32> = pool::Pools::new();
-		pools.add_pool('+', |datum| *datum += 1);
-		pools.add_pool('-', |datum| *datum -= 1);
+		let s = Span::default();
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+		pools.add_pool('-', |datum, _| { *datum -= 1; Ok(()) });
 
 		let program = e::new_closure(vec![
-			e::new_instruction(0, '+'),
-			e::new_exec(),
-			e::new_exec(),
-			e::new_exec(),
-			e::new_instruction(0, '+'),
-			e::new_instruction(0, '-'),
-			e::new_instruction(1, '+'),
+			e::new_instruction(0, '+', s),
+			e::new_exec(s),
+			e::new_exec(s),
+			e::new_exec(s),
+			e::new_instruction(0, '+', s),
+			e::new_instruction(0, '-', s),
+			e::new_instruction(1, '+', s),
 			e::new_loop(vec![
-				e::new_guard(0, false),
-				e::new_guard(1, true)
+				e::new_guard(0, false, s),
+				e::new_guard(1, true, s)
 			], e::new_closure(vec![
-				e::new_exec()
-			]))
+				e::new_exec(s)
+			]), s)
 		]);
 
-		execute(&mut memory, &mut pools, program);
+		execute(&mut memory, &mut pools, &program).unwrap();
 		assert_eq!(memory.get(&0).unwrap_or(&0), &2);
 		assert_eq!(memory.get(&1).unwrap_or(&0), &1);
 	}
@@ -139,40 +336,297 @@ mod from_tree {
 	fn nested_loop() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
 		let mut pools: pool::Pools<i32> = pool::Pools::new();
-		pools.add_pool('+', |datum| *datum += 1);
-		pools.add_pool('-', |datum| *datum -= 1);
+		let s = Span::default();
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+		pools.add_pool('-', |datum, _| { *datum -= 1; Ok(()) });
 
 		let program = e::new_closure(vec![
-			e::new_instruction(1, '+'),
-			e::new_exec(),
-			e::new_exec(),
-			e::new_exec(),
-			e::new_instruction(1, '+'),
-
-			e::new_loop(vec![e::new_guard(1, false)], e::new_closure(vec![
-				e::new_instruction(1, '-'),
-				e::new_exec(),
-				e::new_instruction(1, '-'),
-
-				e::new_instruction(2, '+'),
-				e::new_exec(),
-				e::new_exec(),
-				e::new_exec(),
-				e::new_instruction(2, '+'),
-
-				e::new_instruction(2, '-'),
-				e::new_loop(vec![e::new_guard(2, false)], e::new_closure(vec![
-					e::new_instruction(0, '+'),
-					e::new_exec(),
-					e::new_instruction(0, '+'),
-				])),
-				e::new_instruction(2, '-'),
-			]))
+			e::new_instruction(1, '+', s),
+			e::new_exec(s),
+			e::new_exec(s),
+			e::new_exec(s),
+			e::new_instruction(1, '+', s),
+
+			e::new_loop(vec![e::new_guard(1, false, s)], e::new_closure(vec![
+				e::new_instruction(1, '-', s),
+				e::new_exec(s),
+				e::new_instruction(1, '-', s),
+
+				e::new_instruction(2, '+', s),
+				e::new_exec(s),
+				e::new_exec(s),
+				e::new_exec(s),
+				e::new_instruction(2, '+', s),
+
+				e::new_instruction(2, '-', s),
+				e::new_loop(vec![e::new_guard(2, false, s)], e::new_closure(vec![
+					e::new_instruction(0, '+', s),
+					e::new_exec(s),
+					e::new_instruction(0, '+', s),
+				]), s),
+				e::new_instruction(2, '-', s),
+			]), s)
 		]);
 
-		execute(&mut memory, &mut pools, program);
+		execute(&mut memory, &mut pools, &program).unwrap();
 		assert_eq!(memory.get(&0).unwrap_or(&0), &9);
 		assert_eq!(memory.get(&1).unwrap_or(&0), &0);
 		assert_eq!(memory.get(&2).unwrap_or(&0), &0);
 	}
 }
+
+#[cfg(test)]
+mod errors {
+	use std::collections::HashMap;
+
+	use crate::lagoon::interpreter::expression::factory as e;
+	use crate::lagoon::pool::{self, Fault};
+	use crate::lagoon::span::Span;
+	use super::{Executor, execute};
+	use std::time::Duration;
+	use super::budget::{Budget, Limit};
+	use super::error::RuntimeError;
+
+	#[test]
+	fn unknown_pool() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools: pool::Pools<i32> = pool::Pools::new();
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+
+		let star = Span::new(2, 1, 4, 6);
+		let program = e::new_closure(vec![
+			e::new_instruction(0, '+', Span::new(1, 1, 0, 2)),
+			e::new_instruction(3, '*', star),
+			e::new_exec(Span::new(2, 3, 6, 7))
+		]);
+
+		assert_eq!(
+			execute(&mut memory, &mut pools, &program),
+			Err(RuntimeError::UnknownPool { pool: '*', index: 3, span: star })
+		);
+	}
+
+	#[test]
+	fn fault() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools: pool::Pools<i32> = pool::Pools::new();
+		pools.add_pool('+', |datum, _| {
+			*datum = datum.checked_add(1).ok_or(Fault::Overflow)?;
+			Ok(())
+		});
+		memory.insert(4, i32::MAX);
+
+		let exec = Span::new(1, 3, 2, 3);
+		let program = e::new_closure(vec![
+			e::new_instruction(4, '+', Span::new(1, 1, 0, 2)),
+			e::new_exec(exec)
+		]);
+
+		assert_eq!(
+			execute(&mut memory, &mut pools, &program),
+			Err(RuntimeError::Overflow { pool: '+', index: 4, span: exec })
+		);
+	}
+
+	#[test]
+	fn step_limit() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools: pool::Pools<i32> = pool::Pools::new();
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+
+		// 0+; {0|;}
+		let inner = Span::new(1, 9, 8, 9);
+		let program = e::new_closure(vec![
+			e::new_instruction(0, '+', Span::new(1, 1, 0, 2)),
+			e::new_exec(Span::new(1, 3, 2, 3)),
+			e::new_loop(vec![e::new_guard(0, false, Span::new(1, 6, 5, 6))], e::new_closure(vec![
+				e::new_exec(inner)
+			]), Span::new(1, 5, 4, 10))
+		]);
+
+		let result = Executor::new(&mut memory, &mut pools)
+			.with_step_limit(Some(10))
+			.run(&program);
+		assert_eq!(result, Err(RuntimeError::OutOfBudget {
+			limit: Limit::Steps(10),
+			span: inner,
+			memory: vec![(0, "10".to_string())]
+		}));
+		assert_eq!(memory.get(&0).unwrap_or(&0), &10);
+	}
+
+	#[test]
+	fn budget() {
+		// 0+; {0| 1+ ; 1+}
+		let s = Span::default();
+		let toggle = Span::new(1, 8, 7, 9);
+		let body = Span::new(1, 5, 4, 18);
+		let program = e::new_closure(vec![
+			e::new_instruction(0, '+', s),
+			e::new_exec(s),
+			e::new_loop(vec![e::new_guard(0, false, s)], e::new_closure(vec![
+				e::new_instruction(1, '+', toggle),
+				e::new_exec(s),
+				e::new_instruction(1, '+', s)
+			]), body)
+		]);
+		let run = |budget: Budget| {
+			let mut memory: HashMap<i32, i32> = HashMap::new();
+			let mut pools: pool::Pools<i32> = pool::Pools::new();
+			pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+			Executor::new(&mut memory, &mut pools).with_budget(budget).run(&program)
+		};
+		let memory = |cells: &[(i32, i32)]| cells.iter().map(|(i, v)| (*i, v.to_string())).collect();
+
+		assert_eq!(run(Budget { instructions: Some(7), ..Budget::default() }), Err(RuntimeError::OutOfBudget {
+			limit: Limit::Instructions(7),
+			span: toggle,
+			memory: memory(&[(0, 4), (1, 3)])
+		}));
+		assert_eq!(run(Budget { iterations: Some(5), ..Budget::default() }), Err(RuntimeError::OutOfBudget {
+			limit: Limit::Iterations(5),
+			span: body,
+			memory: memory(&[(0, 6), (1, 5)])
+		}));
+		let time = Duration::from_millis(20);
+		match run(Budget { time: Some(time), ..Budget::default() }) {
+			Err(RuntimeError::OutOfBudget { limit, .. }) => assert_eq!(limit, Limit::Time(time)),
+			other => panic!("expected to run out of time, got {:?}", other)
+		}
+	}
+}
+
+#[cfg(test)]
+mod semantics {
+	use std::collections::HashMap;
+
+	use crate::lagoon::interpreter::expression::factory as e;
+	use crate::lagoon::io::Io;
+	use crate::lagoon::pool::{self, Fault};
+	use crate::lagoon::span::Span;
+	use super::{Executor, Semantics};
+	use super::error::RuntimeError;
+
+	fn pools() -> pool::Pools<i32> {
+		let mut pools: pool::Pools<i32> = pool::Pools::new();
+		pools.add_pool('+', |datum, _| {
+			*datum = datum.checked_add(1).ok_or(Fault::Overflow)?;
+			Ok(())
+		});
+		pools.add_pool('d', |datum, _| {
+			*datum = datum.checked_mul(2).ok_or(Fault::Overflow)?;
+			Ok(())
+		});
+		pools.add_pool('o', |datum, context| Ok(context.io.output.write(datum)?));
+		pools
+	}
+
+	fn run(semantics: Semantics, memory: &mut HashMap<i32, i32>) -> String {
+		let mut pools = pools();
+		let (io, output) = Io::memory("");
+		pools.io = io;
+		let s = Span::default();
+		let program = e::new_closure(vec![
+			e::new_instruction(0, '+', s),
+			e::new_instruction(0, 'd', s),
+			e::new_instruction(0, 'o', s),
+			e::new_exec(s)
+		]);
+		Executor::new(memory, &mut pools)
+			.with_semantics(semantics)
+			.run(&program)
+			.unwrap();
+		output.contents()
+	}
+
+	#[test]
+	fn sequential() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		memory.insert(0, 3);
+		assert_eq!(run(Semantics::Sequential, &mut memory), "8");
+		assert_eq!(memory.get(&0).unwrap_or(&0), &8);
+	}
+
+	#[test]
+	fn simultaneous() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		memory.insert(0, 3);
+		assert_eq!(run(Semantics::Simultaneous, &mut memory), "3");
+		assert_eq!(memory.get(&0).unwrap_or(&0), &7);
+	}
+
+	#[test]
+	fn simultaneous_overflow() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools = pools();
+		memory.insert(1, i32::MAX - 1);
+		let s = Span::default();
+		let program = e::new_closure(vec![
+			e::new_instruction(1, '+', s),
+			e::new_instruction(1, 'd', s),
+			e::new_exec(s)
+		]);
+		let result = Executor::new(&mut memory, &mut pools)
+			.with_semantics(Semantics::Simultaneous)
+			.run(&program);
+		assert_eq!(result, Err(RuntimeError::Overflow { pool: 'd', index: 1, span: s }));
+		assert_eq!(memory.get(&1).unwrap_or(&0), &(i32::MAX - 1));
+	}
+}
+
+#[cfg(test)]
+mod edits {
+	use std::collections::HashMap;
+
+	use crate::lagoon::interpreter::expression::factory as e;
+	use crate::lagoon::pool::{self, Edit};
+	use crate::lagoon::span::Span;
+	use super::{Executor, Semantics};
+	use super::error::RuntimeError;
+
+	fn pools() -> pool::Pools<i32> {
+		let mut pools: pool::Pools<i32> = pool::Pools::new();
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+		pools.add_edit('>', Edit::Shift { pool: '+', by: 1 });
+		pools
+	}
+
+	#[test]
+	fn applied_after_the_step() {
+		for semantics in [Semantics::Sequential, Semantics::Simultaneous] {
+			let mut memory: HashMap<i32, i32> = HashMap::new();
+			let mut pools = pools();
+			let s = Span::default();
+			// 0+ 0>;;; walks the increment along 0, 1 and 2
+			let program = e::new_closure(vec![
+				e::new_instruction(0, '+', s),
+				e::new_instruction(0, '>', s),
+				e::new_exec(s),
+				e::new_exec(s),
+				e::new_exec(s)
+			]);
+			Executor::new(&mut memory, &mut pools)
+				.with_semantics(semantics)
+				.run(&program)
+				.unwrap();
+			for i in 0..3 { assert_eq!(memory.get(&i).unwrap_or(&0), &1); }
+			assert!(pools.get_pool('+').unwrap().set.iter().eq(&[3]));
+		}
+	}
+
+	#[test]
+	fn unknown_target() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools = pools();
+		pools.add_edit('c', Edit::Clear('x'));
+		let exec = Span::new(1, 3, 2, 3);
+		let program = e::new_closure(vec![
+			e::new_instruction(4, 'c', Span::new(1, 1, 0, 2)),
+			e::new_exec(exec)
+		]);
+		assert_eq!(
+			Executor::new(&mut memory, &mut pools).run(&program),
+			Err(RuntimeError::UnknownTarget { pool: 'c', target: 'x', index: 4, span: exec })
+		);
+	}
+}
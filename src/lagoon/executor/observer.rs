@@ -0,0 +1,101 @@
+use crate::lagoon::span::Span;
+
+/// Hooks into an `Executor` to follow a run as it happens. Every method does
+/// nothing unless it is overridden.
+pub trait Observer<T, C> {
+	/// Whether `update` is wanted at all. Without it, the executor does not
+	/// keep hold of the value a cell had before a pool ran over it.
+	const UPDATES: bool = true;
+
+	/// An index was toggled in a pool.
+	fn toggle(&mut self, _pool: char, _index: &T, _span: Span) {}
+	/// A pool took a cell from `before` to `after` during the current `;`.
+	/// With simultaneous semantics every pool starts from the value the cell
+	/// had before the `;`, so the updates of pools sharing a cell add up
+	/// rather than follow on from each other.
+	fn update(&mut self, _pool: char, _index: &T, _before: &C, _after: &C) {}
+	/// A `;` finished, after every `update` it made.
+	fn exec(&mut self, _span: Span) {}
+	/// A `;` stopped partway with an error, after the `update`s it made up
+	/// to then.
+	fn fail(&mut self, _span: Span) {}
+	/// A loop was reached, before its guard is first checked.
+	fn enter(&mut self, _span: Span) {}
+	/// The guard of a loop was checked.
+	fn guard(&mut self, _span: Span, _holds: bool) {}
+	/// A loop finished.
+	fn exit(&mut self, _span: Span) {}
+}
+
+/// The observer an `Executor` starts with, which costs nothing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Quiet;
+
+impl<T, C> Observer<T, C> for Quiet {
+	const UPDATES: bool = false;
+}
+
+impl<T, C, O: Observer<T, C>> Observer<T, C> for &mut O {
+	const UPDATES: bool = O::UPDATES;
+
+	fn toggle(&mut self, pool: char, index: &T, span: Span) { (**self).toggle(pool, index, span) }
+	fn update(&mut self, pool: char, index: &T, before: &C, after: &C) {
+		(**self).update(pool, index, before, after)
+	}
+	fn exec(&mut self, span: Span) { (**self).exec(span) }
+	fn fail(&mut self, span: Span) { (**self).fail(span) }
+	fn enter(&mut self, span: Span) { (**self).enter(span) }
+	fn guard(&mut self, span: Span, holds: bool) { (**self).guard(span, holds) }
+	fn exit(&mut self, span: Span) { (**self).exit(span) }
+}
+
+/// Observes nothing when `None`, so observers can be switched on one by one.
+impl<T, C, O: Observer<T, C>> Observer<T, C> for Option<O> {
+	const UPDATES: bool = O::UPDATES;
+
+	fn toggle(&mut self, pool: char, index: &T, span: Span) {
+		if let Some(o) = self { o.toggle(pool, index, span) }
+	}
+	fn update(&mut self, pool: char, index: &T, before: &C, after: &C) {
+		if let Some(o) = self { o.update(pool, index, before, after) }
+	}
+	fn exec(&mut self, span: Span) { if let Some(o) = self { o.exec(span) } }
+	fn fail(&mut self, span: Span) { if let Some(o) = self { o.fail(span) } }
+	fn enter(&mut self, span: Span) { if let Some(o) = self { o.enter(span) } }
+	fn guard(&mut self, span: Span, holds: bool) { if let Some(o) = self { o.guard(span, holds) } }
+	fn exit(&mut self, span: Span) { if let Some(o) = self { o.exit(span) } }
+}
+
+/// Tells both observers everything, the first one first.
+impl<T, C, A: Observer<T, C>, B: Observer<T, C>> Observer<T, C> for (A, B) {
+	const UPDATES: bool = A::UPDATES || B::UPDATES;
+
+	fn toggle(&mut self, pool: char, index: &T, span: Span) {
+		self.0.toggle(pool, index, span);
+		self.1.toggle(pool, index, span);
+	}
+	fn update(&mut self, pool: char, index: &T, before: &C, after: &C) {
+		self.0.update(pool, index, before, after);
+		self.1.update(pool, index, before, after);
+	}
+	fn exec(&mut self, span: Span) {
+		self.0.exec(span);
+		self.1.exec(span);
+	}
+	fn fail(&mut self, span: Span) {
+		self.0.fail(span);
+		self.1.fail(span);
+	}
+	fn enter(&mut self, span: Span) {
+		self.0.enter(span);
+		self.1.enter(span);
+	}
+	fn guard(&mut self, span: Span, holds: bool) {
+		self.0.guard(span, holds);
+		self.1.guard(span, holds);
+	}
+	fn exit(&mut self, span: Span) {
+		self.0.exit(span);
+		self.1.exit(span);
+	}
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::str::FromStr;
+
+use super::parser::line_comments;
+use super::parser::error::{Diagnostic, ParseError};
+
+/// A cell a program expects to end up holding a value, from the line saying so.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Expected<T, C> {
+	pub line: i32,
+	pub index: T,
+	pub value: C
+}
+
+/// What a program says it should do, in `//!` comments on lines of their own
+/// and outside block comments:
+///
+/// - `//! input: 3 4` gives it a line of input
+/// - `//! output: 0 4 0 12` is a line it should write
+/// - `//! expect 3 = 12` is what a cell should hold once it is done
+#[derive(Debug, PartialEq, Clone)]
+pub struct Expectations<T, C> {
+	pub input: Vec<String>,
+	pub output: Vec<String>,
+	/// The line of the first `output:`
+	pub output_line: i32,
+	pub cells: Vec<Expected<T, C>>
+}
+
+impl<T: Eq + Hash + FromStr + Display, C: PartialEq + Default + FromStr + Display> Expectations<T, C> {
+	/// Picks the directives out of `source`, reporting any it cannot make
+	/// sense of.
+	pub fn parse(source: &str) -> Result<Self, ParseError> {
+		let mut expectations = Self { input: Vec::new(), output: Vec::new(), output_line: 0, cells: Vec::new() };
+		let mut diagnostics = Vec::new();
+		for span in line_comments(source) {
+			let (line, column) = (span.line, span.column);
+			let before = &source[..span.start];
+			let on_its_own = before[before.rfind('\n').map_or(0, |n| n + 1)..].trim().is_empty();
+			let directive = match source[span.start..span.end].strip_prefix("//!") {
+				Some(directive) if on_its_own => directive.trim(),
+				_ => continue
+			};
+			let mut fail = |message: String| diagnostics.push(Diagnostic { line, column, message });
+			if directive.is_empty() { continue; }
+			if let Some(input) = directive.strip_prefix("input:") {
+				expectations.input.push(input.trim().to_string());
+			} else if let Some(output) = directive.strip_prefix("output:") {
+				if expectations.output.is_empty() { expectations.output_line = line; }
+				expectations.output.push(output.trim().to_string());
+			} else if let Some(cell) = directive.strip_prefix("expect").filter(|c| c.is_empty() || c.starts_with(char::is_whitespace)) {
+				let (index, value) = match cell.split_once('=') {
+					Some((index, value)) => (index.trim(), value.trim()),
+					None => {
+						fail(format!("expected `expect <index> = <value>`, found `{}`", directive));
+						continue;
+					}
+				};
+				match (index.parse(), value.parse()) {
+					(Ok(index), Ok(value)) => expectations.cells.push(Expected { line, index, value }),
+					(Err(_), _) => fail(format!("invalid index `{}`", index)),
+					(_, Err(_)) => fail(format!("invalid value `{}`", value))
+				}
+			} else {
+				fail(format!("unknown directive `{}`, expected `input:`, `output:` or `expect`", directive));
+			}
+		}
+		if !diagnostics.is_empty() { return Err(ParseError { diagnostics }); }
+		Ok(expectations)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.input.is_empty() && self.output.is_empty() && self.cells.is_empty()
+	}
+
+	/// Every `input:` line, as the program should read them.
+	pub fn input(&self) -> String {
+		self.input.iter().map(|line| format!("{}\n", line)).collect()
+	}
+
+	/// Everything `output` and `memory` do not live up to, on the line that
+	/// expected it. A cell that was never touched holds the default value.
+	pub fn check(&self, output: &str, memory: &HashMap<T, C>) -> Vec<Diagnostic> {
+		let mut failures = Vec::new();
+		let written: Vec<&str> = output.lines().map(|l| l.trim_end()).collect();
+		if !self.output.is_empty() && written != self.output {
+			failures.push(Diagnostic {
+				line: self.output_line,
+				column: 1,
+				message: format!("expected output `{}`, found `{}`", self.output.join("\\n"), written.join("\\n"))
+			});
+		}
+		for cell in self.cells.iter() {
+			let default = C::default();
+			let value = memory.get(&cell.index).unwrap_or(&default);
+			if *value != cell.value {
+				failures.push(Diagnostic {
+					line: cell.line,
+					column: 1,
+					message: format!("expected cell {} to hold {}, found {}", cell.index, cell.value, value)
+				});
+			}
+		}
+		failures
+	}
+}
+
+#[cfg(test)]
+mod directives {
+	use std::collections::HashMap;
+	use crate::lagoon::Lagoon;
+	use crate::lagoon::io::Io;
+	use crate::lagoon::parser::error::Diagnostic;
+	use super::{Expectations, Expected};
+
+	/// Runs `source` with its `//! input:`, returning what it did not live up to.
+	fn check(source: &str) -> Vec<Diagnostic> {
+		let expectations: Expectations<i32, i32> = Expectations::parse(source).unwrap();
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, output) = Io::memory(&expectations.input());
+		lagoon.pools.io = io;
+		lagoon.append_raw(source).unwrap();
+		lagoon.run().unwrap();
+		lagoon.pools.io.output.finish().unwrap();
+		expectations.check(&output.contents(), &lagoon.memory)
+	}
+
+	#[test]
+	fn parse() {
+		let source = "//! input: 3 4\n0i; //! not one\n  //!   output: 7\n//!\n//! expect 2 = -1\n";
+		let expectations: Expectations<i32, i32> = Expectations::parse(source).unwrap();
+		assert_eq!(expectations.input(), "3 4\n");
+		assert_eq!(expectations.output, vec!["7"]);
+		assert_eq!(expectations.output_line, 3);
+		assert_eq!(expectations.cells, vec![Expected { line: 5, index: 2, value: -1 }]);
+		assert!(Expectations::<i32, i32>::parse("0+;").unwrap().is_empty());
+	}
+
+	#[test]
+	fn invalid() {
+		let source = "//! expect 1\n//! expect x = 1\n  //! expect 1 = 1.5\n//! outptu: 3\n//! expected 3 = 12";
+		let diagnostics = Expectations::<i32, i32>::parse(source).unwrap_err().diagnostics;
+		let at: Vec<(i32, i32)> = diagnostics.iter().map(|d| (d.line, d.column)).collect();
+		assert_eq!(at, vec![(1, 1), (2, 1), (3, 3), (4, 1), (5, 1)]);
+		assert_eq!(diagnostics[1].message, "invalid index `x`");
+		assert_eq!(diagnostics[2].message, "invalid value `1.5`");
+		assert!(diagnostics[4].message.starts_with("unknown directive `expected 3 = 12`"));
+	}
+
+	#[test]
+	fn block_comments() {
+		let source = "/* an example:\n//! expect 0 = 1\n/* nested */ //! output: 2\n*/\n//! expect 0 = 0";
+		let expectations: Expectations<i32, i32> = Expectations::parse(source).unwrap();
+		assert!(expectations.output.is_empty());
+		assert_eq!(expectations.cells, vec![Expected { line: 5, index: 0, value: 0 }]);
+	}
+
+	#[test]
+	fn unmet() {
+		let expectations: Expectations<i32, i32> = Expectations::parse("//! output: 1 2\n//! output: 3\n//! expect 0 = 2\n//! expect 5 = 0").unwrap();
+		let memory: HashMap<i32, i32> = vec![(0, 1)].into_iter().collect();
+		assert_eq!(expectations.check("1 2\n3\n", &[(0, 2)].iter().cloned().collect()), vec![]);
+		let messages: Vec<String> = expectations.check("1 2\n", &memory).iter().map(|d| d.to_string()).collect();
+		assert_eq!(messages, vec![
+			"1:1: expected output `1 2\\n3`, found `1 2`",
+			"3:1: expected cell 0 to hold 2, found 1"
+		]);
+	}
+
+	#[test]
+	fn runs() {
+		assert_eq!(check("//! input: 5\n//! output: 6\n//! expect 0 = 6\n0i; 0i 0+ 0o;"), vec![]);
+		assert_eq!(check("//! expect 0 = 2\n0+;").len(), 1);
+	}
+
+	/// Every example in the README that says what it should do does it.
+	#[test]
+	fn readme() {
+		let readme = include_str!("../../README.md");
+		let examples: Vec<&str> = readme.split("```lag\n").skip(1)
+			.map(|block| block.split("```").next().unwrap())
+			.filter(|block| block.contains("//!"))
+			.collect();
+		assert!(examples.len() >= 2);
+		for example in examples {
+			assert_eq!(check(example), vec![], "{}", example);
+		}
+	}
+}
@@ -0,0 +1,40 @@
+use std::{convert::TryFrom, hash::Hash};
+
+/// What memory can be indexed by. Pools that look at a cell's neighbours
+/// find them through `offset`.
+pub trait Index: Eq + Hash + Ord + Copy {
+	/// The index `by` cells past this one, or `None` if the index type has no
+	/// room for it.
+	fn offset(self, by: i32) -> Option<Self>;
+}
+
+macro_rules! index {
+	($($t:ty),*) => {$(
+		impl Index for $t {
+			fn offset(self, by: i32) -> Option<Self> {
+				let moved = (self as i128).checked_add(by as i128)?;
+				<$t>::try_from(moved).ok()
+			}
+		}
+	)*};
+}
+
+index!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+#[cfg(test)]
+mod offset {
+	use super::Index;
+
+	#[test]
+	fn within_range() {
+		assert_eq!(3i32.offset(-1), Some(2));
+		assert_eq!(3usize.offset(2), Some(5));
+	}
+
+	#[test]
+	fn out_of_range() {
+		assert_eq!(0usize.offset(-1), None);
+		assert_eq!(i32::MAX.offset(1), None);
+		assert_eq!(255u8.offset(1), None);
+	}
+}
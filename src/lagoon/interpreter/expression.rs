@@ -1,15 +1,19 @@
 use std::hash::Hash;
 
+use crate::lagoon::span::Span;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Instruction<T: Eq + Hash + Clone> {
 	pub index: T,
-	pub pool: char
+	pub pool: char,
+	pub span: Span
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Guard<T: Eq + Hash + Clone> {
 	pub index: T,
-	pub negated: bool
+	pub negated: bool,
+	pub span: Span
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -20,32 +24,51 @@ pub struct Closure<T: Eq + Hash + Clone> {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Loop<T: Eq + Hash + Clone> {
 	pub guard: Vec<Guard<T>>,
-	pub closure: Closure<T>
+	pub closure: Closure<T>,
+	pub span: Span
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression<T: Eq + Hash + Clone> {
-	Exec,
+	/// Runs every pool once, from the `;` at the given span
+	Exec(Span),
+	/// Runs every pool `n` times in a row, for the `;`s the span covers. Only
+	/// the optimizer makes these.
+	Repeat(usize, Span),
 	Instruction(Instruction<T>),
 	Loop(Loop<T>)
 }
 
+impl<T: Eq + Hash + Clone> Expression<T> {
+	pub fn span(&self) -> Span {
+		match self {
+			Expression::Exec(span) => *span,
+			Expression::Repeat(_, span) => *span,
+			Expression::Instruction(i) => i.span,
+			Expression::Loop(l) => l.span
+		}
+	}
+}
+
 pub mod factory {
 	use std::hash::Hash;
+	use crate::lagoon::span::Span;
 	use super::{Closure, Expression, Guard, Instruction, Loop};
 
 	pub fn new_instruction<T: Eq + Hash + Clone>(
 		index: T,
-		pool: char
+		pool: char,
+		span: Span
 	) -> Expression<T> {
-		Expression::Instruction(Instruction::<T> {index, pool})
+		Expression::Instruction(Instruction::<T> {index, pool, span})
 	}
 
 	pub fn new_loop<T: Eq + Hash + Clone>(
 		guard: Vec<Guard<T>>,
-		closure: Closure<T>
+		closure: Closure<T>,
+		span: Span
 	) -> Expression<T> {
-		Expression::Loop(Loop::<T> {guard, closure})
+		Expression::Loop(Loop::<T> {guard, closure, span})
 	}
 
 	pub fn new_closure<T: Eq + Hash + Clone>(
@@ -54,11 +77,15 @@ pub mod factory {
 		Closure::<T> {parts}
 	}
 
-	pub fn new_guard<T: Eq + Hash + Clone>(index: T, negated: bool) -> Guard<T> {
-		Guard::<T> {index, negated}
+	pub fn new_guard<T: Eq + Hash + Clone>(
+		index: T,
+		negated: bool,
+		span: Span
+	) -> Guard<T> {
+		Guard::<T> {index, negated, span}
 	}
 
-	pub fn new_exec<T: Eq + Hash + Clone>() -> Expression<T> {
-		Expression::Exec
+	pub fn new_exec<T: Eq + Hash + Clone>(span: Span) -> Expression<T> {
+		Expression::Exec(span)
 	}
 }
@@ -1,82 +1,155 @@
 pub mod expression;
 
-use std::{hash::Hash, str::FromStr};
-use super::parser::tree::{Node, Tree};
+use std::{collections::HashSet, hash::Hash, str::FromStr};
+use super::parser::error::{Diagnostic, ParseError};
+use super::parser::tree::{Declaration, Node, Tree};
+use super::span::Span;
 use self::expression::{Closure, Expression};
 
+/// Turns `tree` into a closure, reporting every index that does not fit in a
+/// `T`.
 pub fn interpret<T: Eq + Hash + Clone + FromStr + Default>(
 	tree: Tree
-) -> Closure<T> {
+) -> Result<Closure<T>, ParseError> {
 	let mut closure: Closure<T> = Closure {parts: Vec::new()};
+	let mut diagnostics: Vec<Diagnostic> = Vec::new();
 	for n in tree.nodes {
-		closure.parts.push(rinterpret::<T>(&n));
+		closure.parts.extend(rinterpret::<T>(&n, &mut diagnostics));
 	}
-	return closure;
+	if !diagnostics.is_empty() { return Err(ParseError { diagnostics }); }
+	return Ok(closure);
 }
 
+/// Declarations only matter before the program runs, so they leave nothing
+/// behind in the closure.
 fn rinterpret<T: Eq + Hash + Clone + FromStr + Default>(
-	node: &Node
-) -> Expression<T> {
+	node: &Node,
+	diagnostics: &mut Vec<Diagnostic>
+) -> Option<Expression<T>> {
 	use self::expression::factory::*;
 
 	match node {
 		Node::Symbol(s) => {
 			let mut text = s.text.clone();
 			let pool: char = text.pop().unwrap();
-			if pool == ';' { return Expression::Exec }
-			let index = match text.parse::<T>() {
-				Ok(n) => n,
-				Err(_) => T::default(),
-			};
-			new_instruction(index, pool)
+			if pool == ';' { return Some(new_exec(s.span)) }
+			let index = index::<T>(&text, s.span, diagnostics);
+			Some(new_instruction(index, pool, s.span))
 		},
 		Node::Loop(l) => {
 			let guard = l.guard.iter().map(|g| {
-				let index = match g.text.parse::<T>() {
-					Ok(n) => n,
-					Err(_) => T::default(),
-				};
-				new_guard(index, g.negated)
+				let index = index::<T>(&g.text, g.span, diagnostics);
+				new_guard(index, g.negated, g.span)
 			}).collect();
 			let closure = new_closure(l.tree.nodes.iter()
-				.map(rinterpret::<T>).collect());
-			new_loop(guard, closure)
+				.filter_map(|n| rinterpret::<T>(n, diagnostics)).collect());
+			Some(new_loop(guard, closure, l.span))
+		},
+		Node::Declaration(_) => None
+	}
+}
+
+/// The index `text` names, or the default after reporting that it does not
+/// fit.
+fn index<T: FromStr + Default>(text: &str, span: Span, diagnostics: &mut Vec<Diagnostic>) -> T {
+	match text.parse::<T>() {
+		Ok(index) => index,
+		Err(_) => {
+			let message = format!("index `{}` does not fit", text);
+			diagnostics.push(Diagnostic { line: span.line, column: span.column, message });
+			T::default()
 		}
 	}
 }
 
+/// Collects the pools `tree` declares, checking that it only uses pools that
+/// are either `known` already or declared before their first use, and that it
+/// declares no pool twice.
+pub fn declarations(
+	tree: &Tree,
+	known: impl Fn(char) -> bool
+) -> Result<Vec<Declaration>, ParseError> {
+	let mut declared: HashSet<char> = HashSet::new();
+	let mut found: Vec<Declaration> = Vec::new();
+	let mut diagnostics: Vec<Diagnostic> = Vec::new();
+	rdeclarations(&tree.nodes, &known, &mut declared, &mut found, &mut diagnostics);
+	if diagnostics.is_empty() {
+		Ok(found)
+	} else {
+		Err(ParseError { diagnostics })
+	}
+}
+
+fn rdeclarations(
+	nodes: &[Node],
+	known: &impl Fn(char) -> bool,
+	declared: &mut HashSet<char>,
+	found: &mut Vec<Declaration>,
+	diagnostics: &mut Vec<Diagnostic>
+) {
+	for node in nodes {
+		let (span, message) = match node {
+			Node::Symbol(s) => {
+				let pool = s.text.chars().last().unwrap();
+				if pool == ';' || known(pool) || declared.contains(&pool) { continue; }
+				(s.span, format!("pool `{}` is not declared", pool))
+			},
+			Node::Loop(l) => {
+				rdeclarations(&l.tree.nodes, known, declared, found, diagnostics);
+				continue;
+			},
+			Node::Declaration(d) => {
+				if !known(d.pool) && declared.insert(d.pool) {
+					found.push(d.clone());
+					continue;
+				}
+				(d.span, format!("pool `{}` already exists", d.pool))
+			}
+		};
+		diagnostics.push(Diagnostic { line: span.line, column: span.column, message });
+	}
+}
+
 #[cfg(test)]
 mod simple {
 	use super::interpret;
 	use crate::lagoon::parser::tree::factory as t;
+	use crate::lagoon::span::Span;
 	use super::expression::factory as e;
 
 	#[test]
 	fn single() {
+		// 0+;
+		let plus = Span::new(1, 1, 0, 2);
+		let exec = Span::new(1, 3, 2, 3);
 		let tree = t::new_tree(vec![
-			t::new_symbol("0+", 1),
-			t::new_exec(1),
+			t::new_symbol("0+", plus),
+			t::new_exec(exec),
 		]);
-		let actual = interpret::<i32>(tree);
+		let actual = interpret::<i32>(tree).unwrap();
 		let expected = e::new_closure(vec![
-			e::new_instruction(0, '+'),
-			e::new_exec()
+			e::new_instruction(0, '+', plus),
+			e::new_exec(exec)
 		]);
 		assert_eq!(actual, expected);
 	}
 
 	#[test]
 	fn multiple() {
+		// 0+ 1+;
+		let zero = Span::new(1, 1, 0, 2);
+		let one = Span::new(1, 4, 3, 5);
+		let exec = Span::new(1, 6, 5, 6);
 		let tree = t::new_tree(vec![
-			t::new_symbol("0+", 1),
-			t::new_symbol("1+", 1),
-			t::new_exec(1),
+			t::new_symbol("0+", zero),
+			t::new_symbol("1+", one),
+			t::new_exec(exec),
 		]);
-		let actual = interpret::<i32>(tree);
+		let actual = interpret::<i32>(tree).unwrap();
 		let expected = e::new_closure(vec![
-			e::new_instruction(0, '+'),
-			e::new_instruction(1, '+'),
-			e::new_exec()
+			e::new_instruction(0, '+', zero),
+			e::new_instruction(1, '+', one),
+			e::new_exec(exec)
 		]);
 		assert_eq!(actual, expected);
 	}
@@ -86,80 +159,171 @@ mod simple {
 mod advanced {
 	use super::interpret;
 	use crate::lagoon::parser::tree::factory as t;
+	use crate::lagoon::span::Span;
 	use super::expression::factory as e;
 
 	#[test]
 	fn nested() {
+		// 0+{0|0-;};
+		let plus = Span::new(1, 1, 0, 2);
+		let lp = Span::new(1, 3, 2, 9);
+		let guard = Span::new(1, 4, 3, 4);
+		let minus = Span::new(1, 6, 5, 7);
+		let inner = Span::new(1, 8, 7, 8);
+		let outer = Span::new(1, 10, 9, 10);
 		let tree = t::new_tree(vec![
-			t::new_symbol("0+", 1),
+			t::new_symbol("0+", plus),
 			t::new_loop(
-				vec![t::new_guard("0", false)],
-				t::new_tree(vec![t::new_symbol("0-", 1), t::new_exec(1)])
+				vec![t::new_guard("0", false, guard)],
+				t::new_tree(vec![t::new_symbol("0-", minus), t::new_exec(inner)]),
+				lp
 			),
-			t::new_exec(1)
+			t::new_exec(outer)
 		]);
-		let actual = interpret::<i32>(tree);
+		let actual = interpret::<i32>(tree).unwrap();
 		let expected = e::new_closure(vec![
-			e::new_instruction(0, '+'),
+			e::new_instruction(0, '+', plus),
 			e::new_loop(
-				vec![e::new_guard(0, false)],
+				vec![e::new_guard(0, false, guard)],
 				e::new_closure(vec![
-					e::new_instruction(0, '-'),
-					e::new_exec()
-				])
+					e::new_instruction(0, '-', minus),
+					e::new_exec(inner)
+				]),
+				lp
 			),
-			e::new_exec()
+			e::new_exec(outer)
 		]);
 		assert_eq!(actual, expected);
 	}
 
 	#[test]
 	fn negated() {
+		// 0+{!0|0-;};
+		let plus = Span::new(1, 1, 0, 2);
+		let lp = Span::new(1, 3, 2, 10);
+		let guard = Span::new(1, 4, 3, 5);
+		let minus = Span::new(1, 7, 6, 8);
+		let inner = Span::new(1, 9, 8, 9);
+		let outer = Span::new(1, 11, 10, 11);
 		let tree = t::new_tree(vec![
-			t::new_symbol("0+", 1),
+			t::new_symbol("0+", plus),
 			t::new_loop(
-				vec![t::new_guard("0", true)],
-				t::new_tree(vec![t::new_symbol("0-", 1), t::new_exec(1)])
+				vec![t::new_guard("0", true, guard)],
+				t::new_tree(vec![t::new_symbol("0-", minus), t::new_exec(inner)]),
+				lp
 			),
-			t::new_exec(1)
+			t::new_exec(outer)
 		]);
-		let actual = interpret(tree);
+		let actual = interpret(tree).unwrap();
 		let expected = e::new_closure(vec![
-			e::new_instruction(0, '+'),
+			e::new_instruction(0, '+', plus),
 			e::new_loop(
-				vec![e::new_guard(0, true)],
+				vec![e::new_guard(0, true, guard)],
 				e::new_closure(vec![
-					e::new_instruction(0, '-'),
-					e::new_exec()
-				])
+					e::new_instruction(0, '-', minus),
+					e::new_exec(inner)
+				]),
+				lp
 			),
-			e::new_exec()
+			e::new_exec(outer)
 		]);
 		assert_eq!(actual, expected);
 	}
 
 	#[test]
 	fn multiple_guards() {
+		// 0+{0,!1|0-;};
+		let plus = Span::new(1, 1, 0, 2);
+		let lp = Span::new(1, 3, 2, 12);
+		let first = Span::new(1, 4, 3, 4);
+		let second = Span::new(1, 6, 5, 7);
+		let minus = Span::new(1, 9, 8, 10);
+		let inner = Span::new(1, 11, 10, 11);
+		let outer = Span::new(1, 13, 12, 13);
 		let tree = t::new_tree(vec![
-			t::new_symbol("0+", 1),
+			t::new_symbol("0+", plus),
 			t::new_loop(
-				vec![t::new_guard("0", false), t::new_guard("1", true)],
-				t::new_tree(vec![t::new_symbol("0-", 1), t::new_exec(1)])
+				vec![t::new_guard("0", false, first), t::new_guard("1", true, second)],
+				t::new_tree(vec![t::new_symbol("0-", minus), t::new_exec(inner)]),
+				lp
 			),
-			t::new_exec(1)
+			t::new_exec(outer)
 		]);
-		let actual = interpret(tree);
+		let actual = interpret(tree).unwrap();
 		let expected = e::new_closure(vec![
-			e::new_instruction(0, '+'),
+			e::new_instruction(0, '+', plus),
 			e::new_loop(
-				vec![e::new_guard(0, false), e::new_guard(1, true)],
+				vec![e::new_guard(0, false, first), e::new_guard(1, true, second)],
 				e::new_closure(vec![
-					e::new_instruction(0, '-'),
-					e::new_exec()
-				])
+					e::new_instruction(0, '-', minus),
+					e::new_exec(inner)
+				]),
+				lp
 			),
-			e::new_exec()
+			e::new_exec(outer)
 		]);
 		assert_eq!(actual, expected);
 	}
 }
+
+#[cfg(test)]
+mod declared {
+	use super::{declarations, interpret};
+	use crate::lagoon::operation::Builtin;
+	use crate::lagoon::parser::parse;
+	use crate::lagoon::parser::tree::Declaration;
+	use crate::lagoon::span::Span;
+	use super::expression::factory as e;
+
+	fn messages(input: &str) -> Vec<String> {
+		let tree = parse(input.to_string()).unwrap();
+		declarations(&tree, |c| c == '+').unwrap_err().diagnostics.into_iter()
+			.map(|d| d.to_string())
+			.collect()
+	}
+
+	#[test]
+	fn collected() {
+		let tree = parse("@d = *3 0d 0+;".to_string()).unwrap();
+		assert_eq!(declarations(&tree, |c| c == '+').unwrap(), vec![Declaration {
+			pool: 'd',
+			operation: Builtin::Multiply(3),
+			span: Span::new(1, 1, 0, 7)
+		}]);
+		assert_eq!(interpret::<i32>(tree).unwrap(), e::new_closure(vec![
+			e::new_instruction(0, 'd', Span::new(1, 9, 8, 10)),
+			e::new_instruction(0, '+', Span::new(1, 12, 11, 13)),
+			e::new_exec(Span::new(1, 14, 13, 14))
+		]));
+	}
+
+	#[test]
+	fn undeclared() {
+		assert_eq!(messages("0+ {0| 1x;}"), vec!["1:8: pool `x` is not declared"]);
+		assert_eq!(messages("0d; @d = +1"), vec!["1:1: pool `d` is not declared"]);
+	}
+
+	#[test]
+	fn redeclared() {
+		assert_eq!(messages("@+ = +2"), vec!["1:1: pool `+` already exists"]);
+		assert_eq!(messages("@d = +2 @d = -2"), vec!["1:9: pool `d` already exists"]);
+	}
+}
+
+#[cfg(test)]
+mod indices {
+	use super::interpret;
+	use crate::lagoon::parser::parse;
+
+	fn messages(input: &str) -> Vec<String> {
+		interpret::<u8>(parse(input.to_string()).unwrap()).unwrap_err().diagnostics.into_iter()
+			.map(|d| d.to_string())
+			.collect()
+	}
+
+	#[test]
+	fn too_large() {
+		assert_eq!(messages("255+ 256+;"), vec!["1:6: index `256` does not fit"]);
+		assert_eq!(messages("{0,99999999999|;}"), vec!["1:4: index `99999999999` does not fit"]);
+	}
+}
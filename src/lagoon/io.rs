@@ -0,0 +1,303 @@
+use std::{cell::RefCell, rc::Rc};
+use std::io::{self, BufRead, Cursor, Write};
+
+use super::cell::{Cell, Overflow};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InputMode {
+	/// Whitespace separated integers, i.e. `3 4\n-12`
+	Integer,
+	/// Every character of the input, as its code point
+	Character
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputMode {
+	/// Every value as an integer, separated by spaces
+	Integer,
+	/// Every value as the character with that code point
+	Character
+}
+
+/// Everything a pool can read from or write to while executing.
+pub struct Io {
+	pub input: Input,
+	pub output: Output
+}
+
+impl Io {
+	pub fn new(input: Input, output: Output) -> Self { Self { input, output } }
+
+	pub fn stdio() -> Self { Self::new(Input::stdin(), Output::stdout()) }
+
+	/// Reads from `input` and collects everything written into the returned
+	/// `Buffer`.
+	pub fn memory(input: &str) -> (Self, Buffer) {
+		let buffer = Buffer::new();
+		let io = Self::new(
+			Input::new(Cursor::new(input.to_string())),
+			Output::new(buffer.clone())
+		);
+		(io, buffer)
+	}
+}
+
+enum Source {
+	Stdin,
+	Reader(Box<dyn BufRead>)
+}
+
+impl Source {
+	fn read_line(&mut self, buffer: &mut String) -> io::Result<usize> {
+		match self {
+			// Standard in is locked one line at a time so that whatever else
+			// reads from it (like the program source) still sees the rest.
+			Source::Stdin => io::stdin().read_line(buffer),
+			Source::Reader(reader) => reader.read_line(buffer)
+		}
+	}
+}
+
+/// The input handle the `i` pool reads from.
+pub struct Input {
+	source: Source,
+	pub mode: InputMode,
+	buffer: String,
+	position: usize
+}
+
+impl Input {
+	pub fn new(reader: impl BufRead + 'static) -> Self {
+		Self::from_source(Source::Reader(Box::new(reader)))
+	}
+
+	pub fn stdin() -> Self { Self::from_source(Source::Stdin) }
+
+	fn from_source(source: Source) -> Self {
+		Self { source, mode: InputMode::Integer, buffer: String::new(), position: 0 }
+	}
+
+	pub fn with_mode(mut self, mode: InputMode) -> Self {
+		self.mode = mode;
+		return self;
+	}
+
+	/// Reads the next value, or `None` once the input is exhausted. A word
+	/// that is not an integer, or a value that does not fit in `C`, is an
+	/// `InvalidData` error.
+	pub fn read<C: Cell>(&mut self) -> io::Result<Option<C>> {
+		match self.mode {
+			InputMode::Integer => self.read_integer(),
+			InputMode::Character => self.read_character()
+		}
+	}
+
+	fn read_integer<C: Cell>(&mut self) -> io::Result<Option<C>> {
+		loop {
+			let rest = &self.buffer[self.position..];
+			let word = rest.trim_start();
+			if !word.is_empty() {
+				let start = self.position + rest.len() - word.len();
+				let end = start + word.find(char::is_whitespace).unwrap_or(word.len());
+				self.position = end;
+				let word = &self.buffer[start..end];
+				return match word.parse() {
+					Ok(value) => Ok(Some(value)),
+					Err(_) => Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("`{}` is not an integer that fits in a cell", word)
+					))
+				};
+			}
+			if !self.fill()? { return Ok(None); }
+		}
+	}
+
+	fn read_character<C: Cell>(&mut self) -> io::Result<Option<C>> {
+		loop {
+			if let Some(c) = self.buffer[self.position..].chars().next() {
+				self.position += c.len_utf8();
+				return match C::from_i64(c as i64, Overflow::Check) {
+					Some(value) => Ok(Some(value)),
+					None => Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("`{}` does not fit in a cell", c)
+					))
+				};
+			}
+			if !self.fill()? { return Ok(None); }
+		}
+	}
+
+	/// Reads whatever is left of the current line, or the next one, including
+	/// its newline. Returns `None` once the input is exhausted.
+	pub fn read_line(&mut self) -> io::Result<Option<String>> {
+		if self.position >= self.buffer.len() && !self.fill()? { return Ok(None); }
+		let line = self.buffer[self.position..].to_string();
+		self.position = self.buffer.len();
+		Ok(Some(line))
+	}
+
+	/// Replaces the buffer with the next line, returning `false` at the end of
+	/// the input.
+	fn fill(&mut self) -> io::Result<bool> {
+		self.buffer.clear();
+		self.position = 0;
+		Ok(self.source.read_line(&mut self.buffer)? > 0)
+	}
+}
+
+/// The output handle the `o` pool writes to.
+pub struct Output {
+	sink: Box<dyn Write>,
+	pub mode: OutputMode,
+	written: bool
+}
+
+impl Output {
+	pub fn new(writer: impl Write + 'static) -> Self {
+		Self { sink: Box::new(writer), mode: OutputMode::Integer, written: false }
+	}
+
+	pub fn stdout() -> Self { Self::new(io::stdout()) }
+
+	pub fn with_mode(mut self, mode: OutputMode) -> Self {
+		self.mode = mode;
+		return self;
+	}
+
+	pub fn write<C: Cell>(&mut self, value: &C) -> io::Result<()> {
+		match self.mode {
+			OutputMode::Integer => {
+				if self.written { write!(self.sink, " ")?; }
+				write!(self.sink, "{}", value)?;
+				self.written = true;
+				Ok(())
+			},
+			OutputMode::Character => self.write_char(value.to_i32().unwrap_or(-1))
+		}
+	}
+
+	/// Writes `value` as the character with that code point, whatever the
+	/// mode.
+	pub fn write_char(&mut self, value: i32) -> io::Result<()> {
+		let c = char::from_u32(value as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+		write!(self.sink, "{}", c)
+	}
+
+	/// Ends the output with a newline if any integers were written, and
+	/// flushes it.
+	pub fn finish(&mut self) -> io::Result<()> {
+		if self.written {
+			writeln!(self.sink)?;
+			self.written = false;
+		}
+		self.sink.flush()
+	}
+}
+
+/// An in-memory `Write` that can be handed to an `Output` while a clone of it
+/// is kept around to read everything back.
+#[derive(Clone, Default)]
+pub struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+	pub fn new() -> Self { Self::default() }
+
+	pub fn bytes(&self) -> Vec<u8> { self.0.borrow().clone() }
+
+	pub fn contents(&self) -> String {
+		String::from_utf8_lossy(&self.0.borrow()).into_owned()
+	}
+}
+
+impl Write for Buffer {
+	fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+		self.0.borrow_mut().extend_from_slice(bytes);
+		Ok(bytes.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+#[cfg(test)]
+mod input {
+	use std::io::{Cursor, ErrorKind};
+	use super::{Input, InputMode};
+
+	#[test]
+	fn integers() {
+		let mut input = Input::new(Cursor::new("3 4\n\n  -12\n"));
+		assert_eq!(input.read().unwrap(), Some(3));
+		assert_eq!(input.read().unwrap(), Some(4));
+		assert_eq!(input.read().unwrap(), Some(-12));
+		assert_eq!(input.read::<i32>().unwrap(), None);
+	}
+
+	#[test]
+	fn not_an_integer() {
+		let mut input = Input::new(Cursor::new("3 four 5"));
+		assert_eq!(input.read().unwrap(), Some(3));
+		assert_eq!(input.read::<i32>().unwrap_err().kind(), ErrorKind::InvalidData);
+		assert_eq!(input.read().unwrap(), Some(5));
+	}
+
+	#[test]
+	fn characters() {
+		let mut input = Input::new(Cursor::new("hi\nå"))
+			.with_mode(InputMode::Character);
+		assert_eq!(input.read().unwrap(), Some('h' as i32));
+		assert_eq!(input.read().unwrap(), Some('i' as i32));
+		assert_eq!(input.read().unwrap(), Some('\n' as i32));
+		assert_eq!(input.read().unwrap(), Some('å' as i32));
+		assert_eq!(input.read::<i32>().unwrap(), None);
+	}
+
+	#[test]
+	fn narrow_cells() {
+		let mut input = Input::new(Cursor::new("255 256"));
+		assert_eq!(input.read().unwrap(), Some(255u8));
+		assert_eq!(input.read::<u8>().unwrap_err().kind(), ErrorKind::InvalidData);
+
+		let mut input = Input::new(Cursor::new("a→")).with_mode(InputMode::Character);
+		assert_eq!(input.read().unwrap(), Some(b'a'));
+		assert_eq!(input.read::<u8>().unwrap_err().kind(), ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn lines() {
+		let mut input = Input::new(Cursor::new("3 4\n:mem\nend"));
+		assert_eq!(input.read().unwrap(), Some(3));
+		assert_eq!(input.read_line().unwrap(), Some(" 4\n".to_string()));
+		assert_eq!(input.read_line().unwrap(), Some(":mem\n".to_string()));
+		assert_eq!(input.read_line().unwrap(), Some("end".to_string()));
+		assert_eq!(input.read_line().unwrap(), None);
+	}
+}
+
+#[cfg(test)]
+mod output {
+	use super::{Buffer, Output, OutputMode};
+
+	#[test]
+	fn integers() {
+		let buffer = Buffer::new();
+		let mut output = Output::new(buffer.clone());
+		output.write(&0).unwrap();
+		output.write(&-4).unwrap();
+		output.write(&12).unwrap();
+		output.finish().unwrap();
+		assert_eq!(buffer.contents(), "0 -4 12\n");
+	}
+
+	#[test]
+	fn characters() {
+		let buffer = Buffer::new();
+		let mut output = Output::new(buffer.clone())
+			.with_mode(OutputMode::Character);
+		output.write(&('h' as i32)).unwrap();
+		output.write(&('i' as i32)).unwrap();
+		output.finish().unwrap();
+		assert_eq!(buffer.contents(), "hi");
+	}
+}
@@ -1,44 +1,385 @@
 #![allow(dead_code)]
 
+pub mod io;
+pub mod error;
+pub mod span;
+pub mod index;
+pub mod cell;
 pub mod pool;
+pub mod operation;
 pub mod parser;
 pub mod interpreter;
 pub mod executor;
+pub mod vm;
+pub mod optimize;
+pub mod debugger;
+pub mod trace;
+pub mod profile;
+pub mod coverage;
+pub mod expect;
 
-use std::{collections::HashMap, hash::Hash, str::FromStr};
+use std::{collections::HashMap, hash::Hash, mem, str::FromStr};
 use interpreter::expression::{Closure, factory::new_closure};
+use error::Error;
+use parser::error::ParseError;
+use executor::{Executor, Semantics, budget::Budget, error::RuntimeError, observer::Observer};
+use optimize::Passes;
+use vm::Vm;
+use index::Index;
+use cell::{Cell, Overflow};
+use operation::Builtin;
+use pool::Fault;
 
-pub struct Lagoon<T: Eq + Hash + Clone> {
-	pub memory: HashMap<T, i32>,
-	pub pools: pool::Pools<T>,
-	pub code: Closure<T>
+/// How the cells of a `Lagoon` behave. Which `Cell` type they are is up to
+/// its `C` parameter.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct LagoonConfig {
+	pub overflow: Overflow
 }
 
-impl<T: Eq + Hash + Clone + Default + FromStr> Lagoon<T> {
+impl LagoonConfig {
+	/// Cells that wrap around, which with `u8` cells is like most brainfuck
+	/// implementations.
+	pub fn wrapping() -> Self {
+		Self { overflow: Overflow::Wrap }
+	}
+}
+
+pub struct Lagoon<T: Eq + Hash + Ord + Clone, C = i32> {
+	pub memory: HashMap<T, C>,
+	pub pools: pool::Pools<T, C>,
+	pub code: Closure<T>,
+	/// How much a single `run` or `eval` may do before giving up
+	pub budget: Budget,
+	/// Whether the pools see each other's changes during a `;`
+	pub semantics: Semantics,
+	pub config: LagoonConfig,
+	/// How to rewrite the code before running it
+	pub passes: Passes
+}
+
+impl<T: Eq + Hash + Ord + Clone + Default + FromStr, C: Cell> Lagoon<T, C> {
 	pub fn new() -> Self {
+		Self::with_config(LagoonConfig::default())
+	}
+
+	pub fn with_config(config: LagoonConfig) -> Self {
 		Self {
 			memory: HashMap::new(),
 			pools: pool::Pools::new(),
-			code: new_closure(vec![])
+			code: new_closure(vec![]),
+			budget: Budget::default(),
+			semantics: Semantics::Sequential,
+			config,
+			passes: Passes::default()
+		}
+	}
+
+	pub fn append(&mut self, input: String) -> Result<(), ParseError> {
+		let mut closure = self.compile(input)?;
+		self.code.parts.append(&mut closure.parts);
+		Ok(())
+	}
+
+	/// Parses `input`, registering the pools it declares once it is known to
+	/// be free of errors.
+	fn compile(&mut self, input: String) -> Result<Closure<T>, ParseError> {
+		let tree = parser::parse(input)?;
+		let pools = &self.pools;
+		let declarations = interpreter::declarations(&tree, |c| pools.map.contains_key(&c))?;
+		let closure = interpreter::interpret(tree)?;
+		for d in declarations {
+			self.pools.add_operation(d.pool, d.operation);
 		}
+		Ok(closure)
+	}
+
+	pub fn append_raw(&mut self, input: &str) -> Result<(), ParseError> {
+		self.append(input.to_string())
 	}
+}
 
+impl<T: Index, C: Cell> Lagoon<T, C> {
 	pub fn gen_pools(&mut self) {
-		self.pools.add_pool('+', |datum| *datum += 1);
-		self.pools.add_pool('-', |datum| *datum -= 1);
+		self.pools.add_operation('+', Builtin::Add(1));
+		self.pools.add_operation('-', Builtin::Subtract(1));
+		self.pools.add_pool('=', |datum, context| {
+			*datum = context.index.offset(-1).map(|left| context.read(&left)).unwrap_or_default();
+			Ok(())
+		});
+		self.pools.add_pool('*', |datum, context| {
+			*datum = context.mul(datum, 2)?;
+			Ok(())
+		});
+
+		self.pools.add_pool('i', |datum, context| {
+			*datum = context.io.input.read()?.ok_or(Fault::InputExhausted)?;
+			Ok(())
+		});
+		self.pools.add_pool('o', |datum, context| Ok(context.io.output.write(datum)?));
+	}
+
+	/// Executes all of `code` against `memory` and `pools`.
+	pub fn run(&mut self) -> Result<(), RuntimeError<T>> {
+		let code = mem::replace(&mut self.code, new_closure(vec![]));
+		let result = self.execute(&code);
+		self.code = code;
+		result
+	}
+
+	/// Executes all of `code` like `run`, but on the `Executor` and without
+	/// optimizing, so that `observer` sees every expression as it was written.
+	pub fn run_observed(&mut self, observer: impl Observer<T, C>) -> Result<(), RuntimeError<T>> {
+		let code = mem::replace(&mut self.code, new_closure(vec![]));
+		let result = Executor::new(&mut self.memory, &mut self.pools)
+			.with_semantics(self.semantics)
+			.with_overflow(self.config.overflow)
+			.with_budget(self.budget)
+			.with_observer(observer)
+			.run(&code);
+		self.code = code;
+		result
+	}
 
-		self.pools.add_pool('i', |datum| *datum += 1);
-		self.pools.add_pool('o', |datum| print!("{}", datum));
+	/// Optimizes `closure` with `passes`, compiles it against the pools as they
+	/// are now and runs it on the `Vm`.
+	fn execute(&mut self, closure: &Closure<T>) -> Result<(), RuntimeError<T>> {
+		let program = vm::compile(&optimize::optimize(closure, self.passes), &self.pools.order);
+		Vm::new(&mut self.memory, &mut self.pools)
+			.with_semantics(self.semantics)
+			.with_overflow(self.config.overflow)
+			.with_budget(self.budget)
+			.run(&program)
 	}
 
-	pub fn append(&mut self, input: String) {
-		let tree = parser::parse(input);
-		let mut closure = interpreter::interpret(tree);
+	/// Forgets the memory, the contents of every pool and the code, but keeps
+	/// the pools themselves.
+	pub fn reset(&mut self) {
+		self.memory.clear();
+		for pool in self.pools.map.values_mut() { pool.set.clear(); }
+		self.code.parts.clear();
+	}
+}
+
+impl<T: Index + Default + FromStr, C: Cell> Lagoon<T, C> {
+	/// Parses `input` and executes just that against the current `memory` and
+	/// `pools`, then appends it to `code`.
+	pub fn eval(&mut self, input: String) -> Result<(), Error<T>> {
+		let mut closure = self.compile(input)?;
+		let result = self.execute(&closure);
 		self.code.parts.append(&mut closure.parts);
+		Ok(result?)
+	}
+}
+
+#[cfg(test)]
+mod builtin {
+	use super::Lagoon;
+	use super::span::Span;
+	use super::executor::{execute, error::RuntimeError};
+	use super::io::Io;
+
+	#[test]
+	fn output() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, output) = Io::memory("");
+		lagoon.pools.io = io;
+		lagoon.append_raw("0+;;; 0+ 0o; 0o 1-; 1- 1o; 1o;").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
+		assert_eq!(output.contents(), "3 -1");
+	}
+
+	#[test]
+	fn output_order() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, output) = Io::memory("");
+		lagoon.pools.io = io;
+		lagoon.append_raw("2+ 2o 0o 1- 1o;").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
+		assert_eq!(output.contents(), "0 -1 1");
+	}
+
+	#[test]
+	fn input() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, _) = Io::memory("7\n-2");
+		lagoon.pools.io = io;
+		lagoon.append_raw("0i; 0i 1i; 1i;").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &7);
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &-2);
 	}
 
-	pub fn append_raw(&mut self, input: &str) {
-		self.append(input.to_string());
+	#[test]
+	fn exhausted_input() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, _) = Io::memory("5");
+		lagoon.pools.io = io;
+		lagoon.append_raw("0i;\n;").unwrap();
+		assert_eq!(
+			lagoon.run(),
+			Err(RuntimeError::InputExhausted { pool: 'i', index: 0, span: Span::new(2, 1, 4, 5) })
+		);
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &5);
+	}
+
+	#[test]
+	fn overflow() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.memory.insert(2, i32::MIN);
+		lagoon.append_raw("2-;").unwrap();
+		assert_eq!(
+			lagoon.run(),
+			Err(RuntimeError::Overflow { pool: '-', index: 2, span: Span::new(1, 3, 2, 3) })
+		);
+	}
+}
+
+#[cfg(test)]
+mod neighbours {
+	use super::Lagoon;
+	use super::executor::{Semantics, error::RuntimeError};
+	use super::span::Span;
+
+	#[test]
+	fn copy() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.append_raw("0+;;; 0+ 1= 2=;").unwrap();
+		lagoon.run().unwrap();
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &3);
+		assert_eq!(lagoon.memory.get(&2).unwrap_or(&0), &3);
+	}
+
+	#[test]
+	fn copy_simultaneous() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.semantics = Semantics::Simultaneous;
+		lagoon.append_raw("0+;;; 0+ 1= 2=;").unwrap();
+		lagoon.run().unwrap();
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &3);
+		assert_eq!(lagoon.memory.get(&2).unwrap_or(&0), &0);
+	}
+
+	#[test]
+	fn double() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.append_raw("0+; 0+ 0*;;;").unwrap();
+		lagoon.run().unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &8);
+
+		lagoon.memory.insert(1, i32::MAX);
+		lagoon.append_raw("0* 1*;").unwrap();
+		assert_eq!(
+			lagoon.run(),
+			Err(RuntimeError::Overflow { pool: '*', index: 1, span: Span::new(1, 6, 5, 6) })
+		);
+	}
+}
+
+#[cfg(test)]
+mod declared {
+	use super::Lagoon;
+	use super::io::Io;
+
+	#[test]
+	fn constant_operations() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.append_raw("@a = +5 @t = *3 @s = =-2 @n = !\n0a 1s;; 0a 0t 1s 1n;").unwrap();
+		lagoon.run().unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &30);
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &2);
+	}
+
+	#[test]
+	fn print_char() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, output) = Io::memory("");
+		lagoon.pools.io = io;
+		lagoon.append_raw("@h = =104 @c = . 0h; 0h 0c; 0c 1o;").unwrap();
+		lagoon.run().unwrap();
+		assert_eq!(output.contents(), "h0");
+	}
+
+	#[test]
+	fn rejected_before_running() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let error = lagoon.append_raw("@d = +1 0d; 1q;").unwrap_err();
+		assert_eq!(error.to_string(), "1:13: pool `q` is not declared");
+		assert!(lagoon.pools.get_pool('d').is_none());
+		assert!(lagoon.code.parts.is_empty());
+	}
+}
+
+#[cfg(test)]
+mod cells {
+	use super::{Lagoon, LagoonConfig};
+	use super::cell::Overflow;
+	use super::executor::{Semantics, error::RuntimeError};
+	use super::span::Span;
+
+	#[test]
+	fn wrapping_bytes() {
+		let mut lagoon: Lagoon<i32, u8> = Lagoon::with_config(LagoonConfig::wrapping());
+		lagoon.gen_pools();
+		lagoon.append_raw("0-; 0- 1*;").unwrap();
+		lagoon.memory.insert(1, 128);
+		lagoon.run().unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &255);
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &0);
+	}
+
+	#[test]
+	fn wrapping_simultaneous() {
+		let mut lagoon: Lagoon<i32, u8> = Lagoon::with_config(LagoonConfig::wrapping());
+		lagoon.gen_pools();
+		lagoon.semantics = Semantics::Simultaneous;
+		lagoon.append_raw("0- 0+ 1-;").unwrap();
+		lagoon.run().unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &255);
+	}
+
+	#[test]
+	fn checked_bytes() {
+		let config = LagoonConfig { overflow: Overflow::Check };
+		let mut lagoon: Lagoon<i32, u8> = Lagoon::with_config(config);
+		lagoon.gen_pools();
+		lagoon.append_raw("0-;").unwrap();
+		assert_eq!(
+			lagoon.run(),
+			Err(RuntimeError::Overflow { pool: '-', index: 0, span: Span::new(1, 3, 2, 3) })
+		);
+	}
+
+	#[test]
+	fn wide() {
+		let mut lagoon: Lagoon<i32, i64> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.append_raw("@s = =2147483647 0s; 0s 0*;").unwrap();
+		lagoon.run().unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &4294967294);
+	}
+
+	#[cfg(feature = "bigint")]
+	#[test]
+	fn unbounded() {
+		use num_bigint::BigInt;
+		let mut lagoon: Lagoon<i32, BigInt> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.append(format!("0+; 0+ 0*{}", ";".repeat(64))).unwrap();
+		lagoon.run().unwrap();
+		assert_eq!(lagoon.memory[&0], BigInt::from(2).pow(64));
 	}
 }
 
@@ -46,14 +387,15 @@ impl<T: Eq + Hash + Clone + Default + FromStr> Lagoon<T> {
 mod full {
 	use super::Lagoon;
 	use super::executor::execute;
+	use super::io::Io;
 
 	#[test]
 	fn simple() {
 		let mut lagoon: Lagoon<i32> = Lagoon::new();
 		lagoon.gen_pools();
-		lagoon.append_raw("0+;");
+		lagoon.append_raw("0+;").unwrap();
 		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
-		execute(&mut lagoon.memory, &mut lagoon.pools, lagoon.code);
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
 		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &1);
 	}
 
@@ -61,10 +403,10 @@ mod full {
 	fn toggle_index() {
 		let mut lagoon: Lagoon<i32> = Lagoon::new();
 		lagoon.gen_pools();
-		lagoon.append_raw("0+0+1+;");
+		lagoon.append_raw("0+0+1+;").unwrap();
 		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
 		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &0);
-		execute(&mut lagoon.memory, &mut lagoon.pools, lagoon.code);
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
 		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
 		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &1);
 	}
@@ -73,8 +415,8 @@ mod full {
 	fn simple_loop() {
 		let mut lagoon: Lagoon<i32> = Lagoon::new();
 		lagoon.gen_pools();
-		lagoon.append_raw("0+;;;0+0-1+{0|;}");
-		execute(&mut lagoon.memory, &mut lagoon.pools, lagoon.code);
+		lagoon.append_raw("0+;;;0+0-1+{0|;}").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
 		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
 		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &3);
 	}
@@ -83,8 +425,8 @@ mod full {
 	fn negated_loop() {
 		let mut lagoon: Lagoon<i32> = Lagoon::new();
 		lagoon.gen_pools();
-		lagoon.append_raw("0+;;;0+0-1+{0,!1|;}");
-		execute(&mut lagoon.memory, &mut lagoon.pools, lagoon.code);
+		lagoon.append_raw("0+;;;0+0-1+{0,!1|;}").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
 		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &2);
 		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &1);
 	}
@@ -93,8 +435,8 @@ mod full {
 	fn nested_loop() {
 		let mut lagoon: Lagoon<i32> = Lagoon::new();
 		lagoon.gen_pools();
-		lagoon.append_raw("1+;;;1+{1|1-;1- 2+;;;2+ 2-{2|0+;0+}2-}");
-		execute(&mut lagoon.memory, &mut lagoon.pools, lagoon.code);
+		lagoon.append_raw("1+;;;1+{1|1-;1- 2+;;;2+ 2-{2|0+;0+}2-}").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
 		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &9);
 		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &0);
 		assert_eq!(lagoon.memory.get(&2).unwrap_or(&0), &0);
@@ -104,11 +446,131 @@ mod full {
 	fn multiplication() {
 		let mut lagoon: Lagoon<i32> = Lagoon::new();
 		lagoon.gen_pools();
-		lagoon.append_raw("0+1+;;;0+;1+{0|0-;0-2+1-{1|;}2+1-2-1+3+{2|;}2-1+3+}");
-		execute(&mut lagoon.memory, &mut lagoon.pools, lagoon.code);
+		lagoon.append_raw("0+1+;;;0+;1+{0|0-;0-2+1-{1|;}2+1-2-1+3+{2|;}2-1+3+}").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
 		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
 		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &4);
 		assert_eq!(lagoon.memory.get(&2).unwrap_or(&0), &0);
 		assert_eq!(lagoon.memory.get(&3).unwrap_or(&0), &12);
 	}
+
+	#[test]
+	fn commented() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.append_raw("
+			0+ ;;; 0+  // set `0` to `3` and then remove `0` from the `+` pool
+			0- 1+      // set `0` to be decremented, and `1` to be incremented
+			{0 | ;}    /* execute the decrement and increment
+			              as long as `0` is truthy */
+		").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &3);
+	}
+
+	#[test]
+	fn multiplication_from_input() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, output) = Io::memory("3 4\n");
+		lagoon.pools.io = io;
+		lagoon.append_raw("0i; 0i 1i; 1i {0|0-;0-2+1-{1|;}2+1-2-1+3+{2|;}2-1+3+} 3o;").unwrap();
+		execute(&mut lagoon.memory, &mut lagoon.pools, &lagoon.code).unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &4);
+		assert_eq!(lagoon.memory.get(&2).unwrap_or(&0), &0);
+		assert_eq!(lagoon.memory.get(&3).unwrap_or(&0), &12);
+		assert_eq!(output.contents(), "12");
+	}
+}
+
+#[cfg(test)]
+mod incremental {
+	use super::Lagoon;
+	use super::error::Error;
+	use super::executor::error::RuntimeError;
+	use super::io::Io;
+	use super::span::Span;
+
+	#[test]
+	fn eval_keeps_state() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.eval("0+;;".to_string()).unwrap();
+		lagoon.eval("0+ 0- 1+ {0|;}".to_string()).unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
+		assert_eq!(lagoon.memory.get(&1).unwrap_or(&0), &2);
+		assert_eq!(lagoon.code.parts.len(), 7);
+	}
+
+	#[test]
+	fn eval_errors() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		assert!(matches!(lagoon.eval("{0".to_string()), Err(Error::Parse(_))));
+		assert_eq!(lagoon.code.parts.len(), 0);
+		assert_eq!(
+			lagoon.eval("0x;".to_string()).unwrap_err().to_string(),
+			"1:1: pool `x` is not declared"
+		);
+		lagoon.memory.insert(0, i32::MAX);
+		assert_eq!(
+			lagoon.eval("0+;".to_string()),
+			Err(Error::Runtime(RuntimeError::Overflow {
+				pool: '+',
+				index: 0,
+				span: Span::new(1, 3, 2, 3)
+			}))
+		);
+	}
+
+	#[test]
+	fn reset() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, output) = Io::memory("");
+		lagoon.pools.io = io;
+		lagoon.eval("0+ 1o;".to_string()).unwrap();
+		lagoon.reset();
+		assert!(lagoon.memory.is_empty());
+		assert!(lagoon.code.parts.is_empty());
+		lagoon.eval("1o;".to_string()).unwrap();
+		assert_eq!(lagoon.memory.get(&0).unwrap_or(&0), &0);
+		assert_eq!(output.contents(), "0 0");
+	}
+}
+
+#[cfg(test)]
+mod budget {
+	use super::Lagoon;
+	use super::executor::budget::{Budget, Limit};
+	use super::executor::error::RuntimeError;
+
+	fn run(source: &str, budget: Budget) -> Result<(), RuntimeError<i32>> {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.budget = budget;
+		lagoon.append_raw(source).unwrap();
+		lagoon.run()
+	}
+
+	#[test]
+	fn reports_memory() {
+		let e = run("0+; {0|;}", Budget { iterations: Some(3), ..Budget::default() }).unwrap_err();
+		assert_eq!(e.to_string(), "1:5: exceeded the limit of 3 loop iterations with memory [0: 4]");
+	}
+
+	#[test]
+	fn limits_skipped_loops() {
+		let budget = Budget { steps: Some(4), ..Budget::default() };
+		assert_eq!(run("0+;; 0+ 0- 1+ {0|;}", budget), Ok(()));
+		match run("0+;;; 0+ 0- 1+ {0|;}", budget) {
+			Err(RuntimeError::OutOfBudget { limit, memory, .. }) => {
+				assert_eq!(limit, Limit::Steps(4));
+				assert_eq!(memory, vec![(0, "2".to_string()), (1, "1".to_string())]);
+			},
+			other => panic!("expected to run out of steps, got {:?}", other)
+		}
+	}
 }
@@ -0,0 +1,117 @@
+use std::{fmt, hash::Hash, str::FromStr};
+
+use super::cell::Cell;
+use super::pool::{Context, Fault, Operation};
+
+/// The operations a pool declared in the source, like `@d = +2`, can be given.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Builtin {
+	/// `+n`: add `n`
+	Add(i32),
+	/// `-n`: subtract `n`
+	Subtract(i32),
+	/// `*n`: multiply by `n`
+	Multiply(i32),
+	/// `=n`: set to `n`
+	Set(i32),
+	/// `!`: negate
+	Negate,
+	/// `.`: write as a character, whatever the output mode
+	PrintChar
+}
+
+impl Builtin {
+	/// Whether the operation written as `c` is followed by a number.
+	pub fn takes_constant(c: char) -> bool {
+		matches!(c, '+' | '-' | '*' | '=')
+	}
+}
+
+impl FromStr for Builtin {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, String> {
+		let mut chars = text.chars();
+		let c = chars.next().ok_or("missing an operation")?;
+		let rest = chars.as_str();
+		if !Builtin::takes_constant(c) {
+			return match (c, rest) {
+				('!', "") => Ok(Builtin::Negate),
+				('.', "") => Ok(Builtin::PrintChar),
+				_ => Err(format!(
+					"unknown operation `{}`, expected `+n`, `-n`, `*n`, `=n`, `!` or `.`",
+					text
+				))
+			};
+		}
+		let n: i32 = match rest.parse() {
+			Ok(n) => n,
+			Err(_) if rest.is_empty() => return Err(format!("`{}` needs a number", c)),
+			Err(_) => return Err(format!("`{}` is not a number", rest))
+		};
+		Ok(match c {
+			'+' => Builtin::Add(n),
+			'-' => Builtin::Subtract(n),
+			'*' => Builtin::Multiply(n),
+			_ => Builtin::Set(n)
+		})
+	}
+}
+
+impl fmt::Display for Builtin {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Builtin::Add(n) => write!(f, "+{}", n),
+			Builtin::Subtract(n) => write!(f, "-{}", n),
+			Builtin::Multiply(n) => write!(f, "*{}", n),
+			Builtin::Set(n) => write!(f, "={}", n),
+			Builtin::Negate => write!(f, "!"),
+			Builtin::PrintChar => write!(f, ".")
+		}
+	}
+}
+
+impl<T: Eq + Hash, C: Cell> Operation<T, C> for Builtin {
+	fn apply(&mut self, datum: &mut C, context: &mut Context<T, C>) -> Result<(), Fault> {
+		match *self {
+			Builtin::Add(n) => *datum = context.add(datum, n as i64)?,
+			Builtin::Subtract(n) => *datum = context.add(datum, -(n as i64))?,
+			Builtin::Multiply(n) => *datum = context.mul(datum, n as i64)?,
+			Builtin::Set(n) => *datum = context.constant(n as i64)?,
+			Builtin::Negate => *datum = datum.neg(context.overflow).ok_or(Fault::Overflow)?,
+			Builtin::PrintChar => context.io.output.write_char(datum.to_i32().unwrap_or(-1))?
+		}
+		Ok(())
+	}
+
+	fn delta(&self) -> Option<i64> {
+		match *self {
+			Builtin::Add(n) => Some(n as i64),
+			Builtin::Subtract(n) => Some(-(n as i64)),
+			_ => None
+		}
+	}
+}
+
+#[cfg(test)]
+mod parse {
+	use super::Builtin;
+
+	#[test]
+	fn valid() {
+		assert_eq!("+2".parse(), Ok(Builtin::Add(2)));
+		assert_eq!("--3".parse(), Ok(Builtin::Subtract(-3)));
+		assert_eq!("*10".parse(), Ok(Builtin::Multiply(10)));
+		assert_eq!("=0".parse(), Ok(Builtin::Set(0)));
+		assert_eq!("!".parse(), Ok(Builtin::Negate));
+		assert_eq!(".".parse(), Ok(Builtin::PrintChar));
+	}
+
+	#[test]
+	fn invalid() {
+		assert_eq!("+".parse::<Builtin>(), Err("`+` needs a number".to_string()));
+		assert_eq!("*x".parse::<Builtin>(), Err("`x` is not a number".to_string()));
+		assert!("!2".parse::<Builtin>().is_err());
+		assert!("%".parse::<Builtin>().is_err());
+	}
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::interpreter::expression::{Closure, Expression, Instruction, Loop};
+
+/// Which rewrites `optimize` makes. Each one leaves memory, pools and output
+/// as they would have been, though errors may point at a different span.
+///
+/// They also assume every pool the closure toggles exists, so a toggle of an
+/// unknown pool may be cancelled out instead of failing.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Passes {
+	/// Removes toggles of the same index in the same pool that undo each other
+	/// before the next `;`, like `0+0+`
+	pub cancel_toggles: bool,
+	/// Turns runs of `;` into a single `Expression::Repeat`
+	pub count_execs: bool,
+	/// Removes loops whose guard cannot hold when they are reached, like a
+	/// second `{0|..}` right after the first
+	pub drop_dead_loops: bool,
+	/// Moves toggles that a loop body makes at its start and undoes at its end
+	/// to before and after the loop
+	pub hoist_toggles: bool
+}
+
+impl Passes {
+	pub fn all() -> Self {
+		Self { cancel_toggles: true, count_execs: true, drop_dead_loops: true, hoist_toggles: true }
+	}
+}
+
+/// Rewrites `closure` with the `passes` that are switched on.
+pub fn optimize<T: Eq + Hash + Clone>(closure: &Closure<T>, passes: Passes) -> Closure<T> {
+	let mut closure = closure.clone();
+	if passes.hoist_toggles { closure = hoist_toggles(closure); }
+	if passes.cancel_toggles { closure = cancel_toggles(closure); }
+	if passes.drop_dead_loops { closure = drop_dead_loops(closure, &mut HashMap::new()); }
+	if passes.count_execs { closure = count_execs(closure); }
+	closure
+}
+
+/// Applies `pass` to the closure of every loop in `closure`.
+fn in_loops<T: Eq + Hash + Clone>(
+	closure: Closure<T>,
+	pass: impl Fn(Closure<T>) -> Closure<T> + Copy
+) -> Closure<T> {
+	let parts = closure.parts.into_iter().map(|e| match e {
+		Expression::Loop(l) => Expression::Loop(Loop { closure: pass(l.closure), ..l }),
+		e => e
+	}).collect();
+	Closure { parts }
+}
+
+fn same<T: Eq + Hash + Clone>(a: &Instruction<T>, b: &Instruction<T>) -> bool {
+	a.index == b.index && a.pool == b.pool
+}
+
+/// Toggles between two `;` can happen in any order, so any that are made an
+/// even number of times do nothing.
+fn cancel_toggles<T: Eq + Hash + Clone>(closure: Closure<T>) -> Closure<T> {
+	let closure = in_loops(closure, cancel_toggles);
+	let mut parts: Vec<Expression<T>> = Vec::new();
+	// Where the current run of toggles started in `parts`
+	let mut run = 0;
+	for e in closure.parts {
+		let i = match e {
+			Expression::Instruction(i) => i,
+			e => {
+				parts.push(e);
+				run = parts.len();
+				continue;
+			}
+		};
+		let twin = parts[run..].iter().position(|p| match p {
+			Expression::Instruction(p) => same(p, &i),
+			_ => false
+		});
+		match twin {
+			Some(at) => { parts.remove(run + at); },
+			None => parts.push(Expression::Instruction(i))
+		}
+	}
+	Closure { parts }
+}
+
+fn count_execs<T: Eq + Hash + Clone>(closure: Closure<T>) -> Closure<T> {
+	let closure = in_loops(closure, count_execs);
+	let mut parts: Vec<Expression<T>> = Vec::new();
+	for e in closure.parts {
+		let span = match e {
+			Expression::Exec(span) => span,
+			e => {
+				parts.push(e);
+				continue;
+			}
+		};
+		let merged = match parts.last() {
+			Some(Expression::Exec(first)) => Expression::Repeat(2, first.to(span)),
+			Some(Expression::Repeat(n, first)) => Expression::Repeat(n + 1, first.to(span)),
+			_ => {
+				parts.push(Expression::Exec(span));
+				continue;
+			}
+		};
+		*parts.last_mut().unwrap() = merged;
+	}
+	Closure { parts }
+}
+
+/// Tracks which cells are known to be zero (`false`) or not (`true`). Only
+/// `;` changes memory, so anything known lasts until the next one. A loop's
+/// guards are known to hold at the start of its body, and a loop with a
+/// single guard leaves it known not to.
+fn drop_dead_loops<T: Eq + Hash + Clone>(
+	closure: Closure<T>,
+	known: &mut HashMap<T, bool>
+) -> Closure<T> {
+	let mut parts: Vec<Expression<T>> = Vec::new();
+	for e in closure.parts {
+		match e {
+			Expression::Exec(_) | Expression::Repeat(..) => known.clear(),
+			Expression::Instruction(_) => {},
+			Expression::Loop(l) => {
+				if l.guard.iter().any(|g| known.get(&g.index) == Some(&g.negated)) { continue; }
+				let mut inside = l.guard.iter().map(|g| (g.index.clone(), !g.negated)).collect();
+				let closure = drop_dead_loops(l.closure, &mut inside);
+				if executes(&closure) { known.clear(); }
+				if let [g] = l.guard.as_slice() { known.insert(g.index.clone(), g.negated); }
+				parts.push(Expression::Loop(Loop { closure, ..l }));
+				continue;
+			}
+		}
+		parts.push(e);
+	}
+	Closure { parts }
+}
+
+fn executes<T: Eq + Hash + Clone>(closure: &Closure<T>) -> bool {
+	closure.parts.iter().any(|e| match e {
+		Expression::Exec(_) | Expression::Repeat(..) => true,
+		Expression::Instruction(_) => false,
+		Expression::Loop(l) => executes(&l.closure)
+	})
+}
+
+/// A toggle made at the start of a loop body and again at its end is undone
+/// by the time the guard is checked, and made again straight after, so it can
+/// be made once before the loop and once after it instead.
+fn hoist_toggles<T: Eq + Hash + Clone>(closure: Closure<T>) -> Closure<T> {
+	let mut parts: Vec<Expression<T>> = Vec::new();
+	for e in closure.parts {
+		let mut l = match e {
+			Expression::Loop(l) => l,
+			e => {
+				parts.push(e);
+				continue;
+			}
+		};
+		let mut body = hoist_toggles(l.closure).parts;
+		l.closure = Closure { parts: Vec::new() };
+		let toggle = |e: &Expression<T>| matches!(e, Expression::Instruction(_));
+		let lead = body.iter().position(|e| !toggle(e)).unwrap_or(body.len());
+		let trail = body.iter().rposition(|e| !toggle(e)).map_or(0, |p| p + 1);
+		// A body of nothing but toggles is left to `cancel_toggles`
+		if lead == body.len() {
+			l.closure = Closure { parts: body };
+			parts.push(Expression::Loop(l));
+			continue;
+		}
+
+		let mut before: Vec<usize> = Vec::new();
+		let mut after: Vec<usize> = Vec::new();
+		for i in 0..lead {
+			let twin = (trail..body.len()).find(|j| !after.contains(j) && match (&body[i], &body[*j]) {
+				(Expression::Instruction(a), Expression::Instruction(b)) => same(a, b),
+				_ => false
+			});
+			if let Some(j) = twin {
+				before.push(i);
+				after.push(j);
+			}
+		}
+		let mut hoisted: Vec<Expression<T>> = Vec::new();
+		for (i, e) in body.drain(..).enumerate() {
+			if before.contains(&i) {
+				parts.push(e);
+			} else if after.contains(&i) {
+				hoisted.push(e);
+			} else {
+				l.closure.parts.push(e);
+			}
+		}
+		parts.push(Expression::Loop(l));
+		parts.append(&mut hoisted);
+	}
+	Closure { parts }
+}
+
+#[cfg(test)]
+mod passes {
+	use crate::lagoon::{interpreter, parser};
+	use crate::lagoon::interpreter::expression::{Closure, Expression};
+	use super::{Passes, optimize};
+
+	/// The closure written back out, without spans.
+	fn shape(closure: &Closure<i32>) -> String {
+		let parts: Vec<String> = closure.parts.iter().map(|e| match e {
+			Expression::Exec(_) => ";".to_string(),
+			Expression::Repeat(n, _) => format!(";x{}", n),
+			Expression::Instruction(i) => format!("{}{}", i.index, i.pool),
+			Expression::Loop(l) => {
+				let guard: Vec<String> = l.guard.iter()
+					.map(|g| format!("{}{}", if g.negated { "!" } else { "" }, g.index))
+					.collect();
+				format!("{{{}|{}}}", guard.join(","), shape(&l.closure))
+			}
+		}).collect();
+		parts.join(" ")
+	}
+
+	fn optimized(source: &str, passes: Passes) -> String {
+		let closure = interpreter::interpret(parser::parse(source.to_string()).unwrap()).unwrap();
+		shape(&optimize(&closure, passes))
+	}
+
+	#[test]
+	fn cancel_toggles() {
+		let passes = Passes { cancel_toggles: true, ..Passes::default() };
+		assert_eq!(optimized("0+ 1- 0+ 0+ ; 0+ {1| 2o 2o ;} 0+", passes), "1- 0+ ; 0+ {1|;} 0+");
+		assert_eq!(optimized("0+ ; 0+", passes), "0+ ; 0+");
+	}
+
+	#[test]
+	fn count_execs() {
+		let passes = Passes { count_execs: true, ..Passes::default() };
+		assert_eq!(optimized("0+ ;;; 0+ ; {0| ;;}", passes), "0+ ;x3 0+ ; {0|;x2}");
+	}
+
+	#[test]
+	fn drop_dead_loops() {
+		let passes = Passes { drop_dead_loops: true, ..Passes::default() };
+		assert_eq!(optimized("{0|;} 1+ {0|;} {0,1|;}", passes), "{0|;} 1+");
+		assert_eq!(optimized("{0|;} ; {0|;}", passes), "{0|;} ; {0|;}");
+		assert_eq!(optimized("{0| {!0|;} ; {!0|;}}", passes), "{0|; {!0|;}}");
+		assert_eq!(optimized("{!1|0+} {!1|;}", passes), "{!1|0+}");
+	}
+
+	#[test]
+	fn hoist_toggles() {
+		let passes = Passes { hoist_toggles: true, ..Passes::default() };
+		assert_eq!(
+			optimized("{0| 2+ 1- 0- ; 1- 2+ 3+}", passes),
+			"2+ 1- {0|0- ; 3+} 1- 2+"
+		);
+		assert_eq!(optimized("{0| 1+ 1+}", passes), "{0|1+ 1+}");
+		assert_eq!(optimized("{0| {1| 1+ ; 1+}}", passes), "1+ {0|{1|;}} 1+");
+	}
+}
+
+#[cfg(test)]
+mod runs {
+	use std::collections::HashMap;
+
+	use crate::lagoon::Lagoon;
+	use crate::lagoon::executor::execute;
+	use crate::lagoon::io::Io;
+	use super::{Passes, optimize};
+
+	const PROGRAMS: [&str; 5] = [
+		"0i 1i; 0i 1i
+		{0 | 0-;0- 2+ 1- {1 | ;} 2+ 1- 2- 1+ 3+ {2 | ;} 2- 1+ 3+}
+		0o 1o 2o 3o;",
+		"0+;;; 0+ 0+ 0+ 0- 1+ {0|;} {0|;} 1o 1o 1o;",
+		"0+;;; 0+ {0| 1+ 0- ; 0- 1+ {!0,!2| 2+;2+}} 1o 2o;",
+		"0i; 0i {0| 1+ 1o 0- ; 1+ 1o 0-} {0| 0o;}",
+		"0+ 1+ ;; 0+ {0,1| 0- ;} 0- {!0| 1* ;; 1* 0+;0+} 1o;"
+	];
+
+	fn run(source: &str, passes: Passes) -> (HashMap<i32, i32>, String) {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, output) = Io::memory("3 4");
+		lagoon.pools.io = io;
+		lagoon.append_raw(source).unwrap();
+		let code = optimize(&lagoon.code, passes);
+		execute(&mut lagoon.memory, &mut lagoon.pools, &code).unwrap();
+		(lagoon.memory, output.contents())
+	}
+
+	#[test]
+	fn each_pass() {
+		let passes = [
+			Passes { cancel_toggles: true, ..Passes::default() },
+			Passes { count_execs: true, ..Passes::default() },
+			Passes { drop_dead_loops: true, ..Passes::default() },
+			Passes { hoist_toggles: true, ..Passes::default() },
+			Passes::all()
+		];
+		for source in PROGRAMS.iter() {
+			let expected = run(source, Passes::default());
+			for p in passes.iter() {
+				assert_eq!(run(source, *p), expected, "{:?} on {}", p, source);
+			}
+		}
+	}
+}
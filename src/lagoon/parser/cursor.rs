@@ -0,0 +1,159 @@
+use crate::lagoon::span::Span;
+use super::error::Diagnostic;
+
+/// Walks over the source one `char` at a time, keeping track of the current
+/// line and column and skipping anything the parser should never see
+/// (whitespace and comments). Problems found along the way are collected in
+/// `diagnostics`.
+pub struct Cursor<'a> {
+	source: &'a str,
+	offset: usize,
+	pub line: i32,
+	pub column: i32,
+	pub diagnostics: Vec<Diagnostic>
+}
+
+impl<'a> Cursor<'a> {
+	pub fn new(source: &'a str) -> Self {
+		Self { source, offset: 0, line: 1, column: 1, diagnostics: Vec::new() }
+	}
+
+	/// An empty span right before the next `char`.
+	pub fn mark(&self) -> Span {
+		Span::new(self.line, self.column, self.offset, self.offset)
+	}
+
+	/// The span from `mark` up to the next `char`.
+	pub fn span_from(&self, mark: Span) -> Span {
+		Span { end: self.offset, ..mark }
+	}
+
+	pub fn error(&mut self, at: Span, message: String) {
+		self.diagnostics.push(Diagnostic { line: at.line, column: at.column, message });
+	}
+
+	pub fn peek(&self) -> Option<char> {
+		self.source[self.offset..].chars().next()
+	}
+
+	fn peek_second(&self) -> Option<char> {
+		self.source[self.offset..].chars().nth(1)
+	}
+
+	pub fn next(&mut self) -> Option<char> {
+		let c = self.peek()?;
+		self.offset += c.len_utf8();
+		if c == '\n' {
+			self.line += 1;
+			self.column = 1;
+		} else {
+			self.column += 1;
+		}
+		Some(c)
+	}
+
+	/// Skips whitespace, `// line` comments and `/* block */` comments, where
+	/// block comments may be nested.
+	pub fn skip_trivia(&mut self) {
+		while let Some(c) = self.peek() {
+			if c.is_whitespace() {
+				self.next();
+			} else if c == '/' && self.peek_second() == Some('/') {
+				self.skip_line_comment();
+			} else if c == '/' && self.peek_second() == Some('*') {
+				self.skip_block_comment();
+			} else {
+				break;
+			}
+		}
+	}
+
+	/// Skips to the end of the next `// line` comment that is not inside a
+	/// block comment, returning where it is, or `None` when there is none.
+	pub fn next_line_comment(&mut self) -> Option<Span> {
+		loop {
+			match (self.peek()?, self.peek_second()) {
+				('/', Some('/')) => {
+					let mark = self.mark();
+					self.skip_line_comment();
+					return Some(self.span_from(mark));
+				},
+				('/', Some('*')) => self.skip_block_comment(),
+				_ => { self.next(); }
+			}
+		}
+	}
+
+	fn skip_line_comment(&mut self) {
+		while let Some(c) = self.peek() {
+			if c == '\n' { break; }
+			self.next();
+		}
+	}
+
+	fn skip_block_comment(&mut self) {
+		let start = self.mark();
+		let mut depth = 0;
+		while let Some(c) = self.next() {
+			if c == '/' && self.peek() == Some('*') {
+				self.next();
+				depth += 1;
+			} else if c == '*' && self.peek() == Some('/') {
+				self.next();
+				depth -= 1;
+				if depth == 0 { return; }
+			}
+		}
+		self.error(start, "unclosed block comment".to_string());
+	}
+}
+
+#[cfg(test)]
+mod trivia {
+	use crate::lagoon::span::Span;
+	use super::Cursor;
+
+	#[test]
+	fn line_comment() {
+		let mut cursor = Cursor::new("// a comment\n0+");
+		cursor.skip_trivia();
+		assert_eq!(cursor.mark(), Span::new(2, 1, 13, 13));
+		assert_eq!(cursor.next(), Some('0'));
+	}
+
+	#[test]
+	fn next_line_comment() {
+		let mut cursor = Cursor::new("0+ // one\n/* // not\n */ 1+;// two");
+		assert_eq!(cursor.next_line_comment(), Some(Span::new(1, 4, 3, 9)));
+		assert_eq!(cursor.next_line_comment(), Some(Span::new(3, 8, 27, 33)));
+		assert_eq!(cursor.next_line_comment(), None);
+	}
+
+	#[test]
+	fn nested_block_comment() {
+		let mut cursor = Cursor::new("/* a /* nested */\n comment */0+");
+		cursor.skip_trivia();
+		assert_eq!(cursor.mark(), Span::new(2, 12, 29, 29));
+		assert_eq!(cursor.next(), Some('0'));
+		assert!(cursor.diagnostics.is_empty());
+	}
+
+	#[test]
+	fn unclosed_block_comment() {
+		let mut cursor = Cursor::new("0+ /* /* */");
+		cursor.next();
+		cursor.next();
+		cursor.skip_trivia();
+		assert_eq!(cursor.next(), None);
+		assert_eq!(cursor.diagnostics.len(), 1);
+		assert_eq!(cursor.diagnostics[0].line, 1);
+		assert_eq!(cursor.diagnostics[0].column, 4);
+	}
+
+	#[test]
+	fn lone_slash() {
+		let mut cursor = Cursor::new(" /0");
+		cursor.skip_trivia();
+		assert_eq!(cursor.next(), Some('/'));
+	}
+}
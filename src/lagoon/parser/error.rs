@@ -0,0 +1,32 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+	pub line: i32,
+	pub column: i32,
+	pub message: String
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}: {}", self.line, self.column, self.message)
+	}
+}
+
+/// Everything that was wrong with a program, in the order it was found.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+	pub diagnostics: Vec<Diagnostic>
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (i, d) in self.diagnostics.iter().enumerate() {
+			if i > 0 { writeln!(f)?; }
+			write!(f, "{}", d)?;
+		}
+		Ok(())
+	}
+}
+
+impl Error for ParseError {}
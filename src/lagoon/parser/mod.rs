@@ -1,79 +1,218 @@
 pub mod tree;
+pub mod error;
+mod cursor;
 
-use std::str::Chars;
-
-use tree::{Guard, Tree};
+use tree::{Declaration, Guard, Tree};
 use tree::factory::*;
+use error::ParseError;
+use cursor::Cursor;
+use super::operation::Builtin;
+use super::span::Span;
 
-pub fn parse(input: String) -> Tree {
+pub fn parse(input: String) -> Result<Tree, ParseError> {
 	let tree: Tree = Tree::empty();
-	let line = 1;
-	rparse(tree, &mut input.chars(), line)
+	let mut cursor = Cursor::new(&input);
+	let tree = rparse(tree, &mut cursor, None);
+	if cursor.diagnostics.is_empty() {
+		Ok(tree)
+	} else {
+		Err(ParseError { diagnostics: cursor.diagnostics })
+	}
+}
+
+/// Where each `// line` comment in `input` is, leaving out those inside block
+/// comments.
+pub fn line_comments(input: &str) -> Vec<Span> {
+	let mut cursor = Cursor::new(input);
+	let mut comments = Vec::new();
+	while let Some(span) = cursor.next_line_comment() {
+		comments.push(span);
+	}
+	comments
 }
 
-fn rparse(mut tree: Tree, chars: &mut Chars, mut line: i32) -> Tree {
+/// Whether `input` stops partway through a loop or a block comment, meaning
+/// more input could still turn it into a complete program. A stray `}` does
+/// not close a `{` that comes after it.
+pub fn is_incomplete(input: &str) -> bool {
+	let mut cursor = Cursor::new(input);
+	let mut depth = 0;
+	loop {
+		cursor.skip_trivia();
+		match cursor.next() {
+			Some('{') => depth += 1,
+			Some('}') => depth = (depth - 1).max(0),
+			Some(_) => {},
+			None => break
+		}
+	}
+	depth > 0 || !cursor.diagnostics.is_empty()
+}
+
+/// Parses until the end of the input, or the `}` closing the loop opened at
+/// `open`.
+fn rparse(mut tree: Tree, cursor: &mut Cursor, open: Option<Span>) -> Tree {
 	let mut current: String = String::new();
-	while let Some(c) = chars.next() {
-		if c == ' ' || c == '\t' { continue; }
-		if c == '\n' {
-			line += 1;
+	let mut start = cursor.mark();
+	loop {
+		cursor.skip_trivia();
+		let mark = cursor.mark();
+		let c = match cursor.next() {
+			Some(c) => c,
+			None => break
+		};
+		if c.is_ascii_digit() {
+			if current.is_empty() { start = mark; }
+			current.push(c);
+			continue;
+		}
+		if !current.is_empty() && (c == '{' || c == '}' || c == ';' || c == '@') {
+			cursor.error(start, format!("index `{}` is not followed by a pool", current));
+			current.clear();
+		}
+
+		if c == '@' {
+			if let Some(declaration) = find_declaration(cursor, mark) {
+				if open.is_some() {
+					cursor.error(mark, "pools cannot be declared inside a loop".to_string());
+				} else {
+					tree.push(tree::Node::Declaration(declaration));
+				}
+			}
 		} else if c == '{' {
-			let guard: Vec<Guard> = find_guard(chars, line);
-			let t: Tree = rparse(Tree::empty(), chars, line);
-			tree.push(new_loop(guard, t));
+			if let Some(guard) = find_guard(cursor, mark) {
+				let t: Tree = rparse(Tree::empty(), cursor, Some(mark));
+				tree.push(new_loop(guard, t, cursor.span_from(mark)));
+			}
 		} else if c == '}' {
-			return tree;
-		} else if c.is_numeric() {
-			current.push(c);
+			if open.is_some() { return tree; }
+			cursor.error(mark, "unmatched `}`".to_string());
 		} else {
+			if current.is_empty() {
+				if c != ';' { cursor.error(mark, format!("pool `{}` has no index", c)); }
+				start = mark;
+			}
 			current.push(c);
-			tree.push(new_symbol(&current, line));
+			tree.push(new_symbol(&current, cursor.span_from(start)));
 			current.clear();
 		}
 	}
+	if !current.is_empty() {
+		cursor.error(start, format!("index `{}` is not followed by a pool", current));
+	}
+	if let Some(open) = open {
+		cursor.error(open, "unclosed `{`".to_string());
+	}
 	tree
 }
 
-fn find_guard(chars: &mut Chars<'_>, mut _line: i32) -> Vec<Guard> {
+/// Whether `c` can name a pool in a declaration.
+fn is_pool(c: char) -> bool {
+	!c.is_ascii_digit() && !c.is_whitespace() && !"{}|,!;@=/".contains(c)
+}
+
+/// Reads the rest of the declaration started by the `@` at `at`, up to the end
+/// of its operation.
+fn find_declaration(cursor: &mut Cursor, at: Span) -> Option<Declaration> {
+	// Problems are reported as they are found, but the rest is still read so
+	// that it is not mistaken for instructions.
+	let mut valid = true;
+	cursor.skip_trivia();
+	let mark = cursor.mark();
+	let pool = match cursor.next() {
+		Some(c) => c,
+		None => {
+			cursor.error(at, "declaration is missing a pool".to_string());
+			return None;
+		}
+	};
+	if !is_pool(pool) {
+		cursor.error(mark, format!("`{}` cannot name a pool", pool));
+		valid = false;
+	}
+	cursor.skip_trivia();
+	if cursor.peek() == Some('=') {
+		cursor.next();
+		cursor.skip_trivia();
+	} else {
+		cursor.error(cursor.mark(), format!("expected `=` after `@{}`", pool));
+		valid = false;
+	}
+	let mark = cursor.mark();
+	let mut text = String::new();
+	if let Some(c) = cursor.next() {
+		text.push(c);
+		if Builtin::takes_constant(c) {
+			if cursor.peek() == Some('-') { text.push('-'); cursor.next(); }
+			while let Some(d) = cursor.peek().filter(|d| d.is_ascii_digit()) {
+				text.push(d);
+				cursor.next();
+			}
+		}
+	}
+	match text.parse() {
+		Ok(operation) if valid => Some(Declaration { pool, operation, span: cursor.span_from(at) }),
+		Ok(_) => None,
+		Err(message) => {
+			cursor.error(mark, message);
+			None
+		}
+	}
+}
+
+/// Reads the guard of the loop opened at `open`, up to and including the `|`.
+/// Returns `None` when there is no `|`, in which case the loop has no closure
+/// left to parse.
+fn find_guard(cursor: &mut Cursor, open: Span) -> Option<Vec<Guard>> {
 	let mut guards: Vec<Guard> = Vec::new();
 	let mut text: String = String::new();
 	let mut negated: bool = false;
-	while let Some(c) = chars.next() {
-		if c == ' ' || c == '\t' { continue; }
-		if c == '|' {
-			guards.push(Guard { text, negated });
-			break;
-		}
-
-		if c == '\n' {
-			_line += 1;
-		} else if c == ',' {
-			guards.push(Guard { text, negated });
+	let mut span: Option<Span> = None;
+	loop {
+		cursor.skip_trivia();
+		let mark = cursor.mark();
+		let c = match cursor.next() {
+			Some(c) => c,
+			None => break
+		};
+		if c == '|' || c == ',' {
+			if text.is_empty() {
+				cursor.error(mark, "empty loop guard".to_string());
+			}
+			guards.push(Guard { text, negated, span: span.unwrap_or(mark) });
+			if c == '|' { return Some(guards); }
 			text = String::new();
 			negated = false;
-		} else if c == '!' {
+			span = None;
+		} else if c == '}' {
+			break;
+		} else if c == '!' && text.is_empty() && !negated {
 			negated = true;
-		} else if !c.is_numeric() {
-			// TODO: throw error
-		} else {
+			span = Some(cursor.span_from(mark));
+		} else if c.is_ascii_digit() {
 			text.push(c);
+			span = Some(cursor.span_from(span.unwrap_or(mark)));
+		} else {
+			cursor.error(mark, format!("unexpected `{}` in loop guard", c));
 		}
 	}
-	return guards;
+	cursor.error(open, "loop guard is missing a `|`".to_string());
+	return None;
 }
 
 #[cfg(test)]
 mod simple {
 	use super::parse;
 	use super::tree::factory::*;
+	use crate::lagoon::span::Span;
 
 	#[test]
 	fn single() {
 		let input = "0+;".to_string();
-		let tree = parse(input);
+		let tree = parse(input).unwrap();
 		let expected = new_tree(vec![
-			new_symbol("0+", 1),
-			new_exec(1),
+			new_symbol("0+", Span::new(1, 1, 0, 2)),
+			new_exec(Span::new(1, 3, 2, 3)),
 		]);
 		assert_eq!(tree, expected);
 	}
@@ -81,13 +220,25 @@ mod simple {
 	#[test]
 	fn multiple() {
 		let input = "0+ 1+ 123+ 321o;".to_string();
-		let tree = parse(input);
+		let tree = parse(input).unwrap();
+		let expected = new_tree(vec![
+			new_symbol("0+", Span::new(1, 1, 0, 2)),
+			new_symbol("1+", Span::new(1, 4, 3, 5)),
+			new_symbol("123+", Span::new(1, 7, 6, 10)),
+			new_symbol("321o", Span::new(1, 12, 11, 15)),
+			new_exec(Span::new(1, 16, 15, 16))
+		]);
+		assert_eq!(tree, expected);
+	}
+
+	#[test]
+	fn spread_out() {
+		let input = "0+\n  12 +  ;".to_string();
+		let tree = parse(input).unwrap();
 		let expected = new_tree(vec![
-			new_symbol("0+", 1),
-			new_symbol("1+", 1),
-			new_symbol("123+", 1),
-			new_symbol("321o", 1),
-			new_exec(1)
+			new_symbol("0+", Span::new(1, 1, 0, 2)),
+			new_symbol("12+", Span::new(2, 3, 5, 9)),
+			new_exec(Span::new(2, 9, 11, 12))
 		]);
 		assert_eq!(tree, expected);
 	}
@@ -98,18 +249,23 @@ mod simple {
 mod advanced {
 	use super::parse;
 	use super::tree::factory::*;
+	use crate::lagoon::span::Span;
 
 	#[test]
 	fn nested() {
 		let input = "0+{0|0-;};".to_string();
-		let tree = parse(input);
+		let tree = parse(input).unwrap();
 		let expected = new_tree(vec![
-			new_symbol("0+", 1),
+			new_symbol("0+", Span::new(1, 1, 0, 2)),
 			new_loop(
-				vec![new_guard("0", false)],
-				new_tree(vec![new_symbol("0-", 1), new_exec(1)])
+				vec![new_guard("0", false, Span::new(1, 4, 3, 4))],
+				new_tree(vec![
+					new_symbol("0-", Span::new(1, 6, 5, 7)),
+					new_exec(Span::new(1, 8, 7, 8))
+				]),
+				Span::new(1, 3, 2, 9)
 			),
-			new_exec(1)
+			new_exec(Span::new(1, 10, 9, 10))
 		]);
 		assert_eq!(tree, expected);
 	}
@@ -117,14 +273,18 @@ mod advanced {
 	#[test]
 	fn negated() {
 		let input = "0+{!0|0-;};".to_string();
-		let tree = parse(input);
+		let tree = parse(input).unwrap();
 		let expected = new_tree(vec![
-			new_symbol("0+", 1),
+			new_symbol("0+", Span::new(1, 1, 0, 2)),
 			new_loop(
-				vec![new_guard("0", true)],
-				new_tree(vec![new_symbol("0-", 1), new_exec(1)])
+				vec![new_guard("0", true, Span::new(1, 4, 3, 5))],
+				new_tree(vec![
+					new_symbol("0-", Span::new(1, 7, 6, 8)),
+					new_exec(Span::new(1, 9, 8, 9))
+				]),
+				Span::new(1, 3, 2, 10)
 			),
-			new_exec(1)
+			new_exec(Span::new(1, 11, 10, 11))
 		]);
 		assert_eq!(tree, expected);
 	}
@@ -132,15 +292,254 @@ mod advanced {
 	#[test]
 	fn multiple_guards() {
 		let input = "0+{0,!1|0-;};".to_string();
-		let tree = parse(input);
+		let tree = parse(input).unwrap();
 		let expected = new_tree(vec![
-			new_symbol("0+", 1),
+			new_symbol("0+", Span::new(1, 1, 0, 2)),
 			new_loop(
-				vec![new_guard("0", false), new_guard("1", true)],
-				new_tree(vec![new_symbol("0-", 1), new_exec(1)])
+				vec![
+					new_guard("0", false, Span::new(1, 4, 3, 4)),
+					new_guard("1", true, Span::new(1, 6, 5, 7))
+				],
+				new_tree(vec![
+					new_symbol("0-", Span::new(1, 9, 8, 10)),
+					new_exec(Span::new(1, 11, 10, 11))
+				]),
+				Span::new(1, 3, 2, 12)
 			),
-			new_exec(1)
+			new_exec(Span::new(1, 13, 12, 13))
+		]);
+		assert_eq!(tree, expected);
+	}
+}
+
+#[cfg(test)]
+mod comments {
+	use super::parse;
+	use super::tree::factory::*;
+	use crate::lagoon::span::Span;
+
+	#[test]
+	fn line_comment() {
+		let input = "0+; // increment `0` once\n1+;".to_string();
+		let tree = parse(input).unwrap();
+		let expected = new_tree(vec![
+			new_symbol("0+", Span::new(1, 1, 0, 2)),
+			new_exec(Span::new(1, 3, 2, 3)),
+			new_symbol("1+", Span::new(2, 1, 26, 28)),
+			new_exec(Span::new(2, 3, 28, 29))
+		]);
+		assert_eq!(tree, expected);
+	}
+
+	#[test]
+	fn block_comment() {
+		let input = "0+ /* a /* nested */\ncomment */ ;".to_string();
+		let tree = parse(input).unwrap();
+		let expected = new_tree(vec![
+			new_symbol("0+", Span::new(1, 1, 0, 2)),
+			new_exec(Span::new(2, 12, 32, 33))
+		]);
+		assert_eq!(tree, expected);
+	}
+
+	#[test]
+	fn inside_guard() {
+		let input = "{0, // the counter\n!1 /* the flag */ | ;}".to_string();
+		let tree = parse(input).unwrap();
+		let expected = new_tree(vec![
+			new_loop(
+				vec![
+					new_guard("0", false, Span::new(1, 2, 1, 2)),
+					new_guard("1", true, Span::new(2, 1, 19, 21))
+				],
+				new_tree(vec![new_exec(Span::new(2, 21, 39, 40))]),
+				Span::new(1, 1, 0, 41)
+			)
+		]);
+		assert_eq!(tree, expected);
+	}
+
+	#[test]
+	fn inside_closure() {
+		let input = "{0 |\n  0-; // decrement `0`\n  /* 1+ */ 2+;\n}".to_string();
+		let tree = parse(input).unwrap();
+		let expected = new_tree(vec![
+			new_loop(
+				vec![new_guard("0", false, Span::new(1, 2, 1, 2))],
+				new_tree(vec![
+					new_symbol("0-", Span::new(2, 3, 7, 9)),
+					new_exec(Span::new(2, 5, 9, 10)),
+					new_symbol("2+", Span::new(3, 12, 39, 41)),
+					new_exec(Span::new(3, 14, 41, 42))
+				]),
+				Span::new(1, 1, 0, 44)
+			)
+		]);
+		assert_eq!(tree, expected);
+	}
+}
+
+#[cfg(test)]
+mod declarations {
+	use super::parse;
+	use super::tree::factory::*;
+	use crate::lagoon::operation::Builtin;
+	use crate::lagoon::span::Span;
+
+	#[test]
+	fn simple() {
+		let input = "@d = +2
+@n=! 0d;".to_string();
+		let tree = parse(input).unwrap();
+		let expected = new_tree(vec![
+			new_declaration('d', Builtin::Add(2), Span::new(1, 1, 0, 7)),
+			new_declaration('n', Builtin::Negate, Span::new(2, 1, 8, 12)),
+			new_symbol("0d", Span::new(2, 6, 13, 15)),
+			new_exec(Span::new(2, 8, 15, 16))
+		]);
+		assert_eq!(tree, expected);
+	}
+
+	#[test]
+	fn negative_constant() {
+		let tree = parse("@x = *-3".to_string()).unwrap();
+		let expected = new_tree(vec![
+			new_declaration('x', Builtin::Multiply(-3), Span::new(1, 1, 0, 8))
 		]);
 		assert_eq!(tree, expected);
 	}
 }
+
+#[cfg(test)]
+mod incomplete {
+	use super::is_incomplete;
+
+	#[test]
+	fn complete() {
+		assert!(!is_incomplete("0+; {0|;}"));
+		assert!(!is_incomplete("0+; // {"));
+		assert!(!is_incomplete("0+; }"));
+	}
+
+	#[test]
+	fn open_loop() {
+		assert!(is_incomplete("{0|"));
+		assert!(is_incomplete("{0| {1|;} /* } */"));
+		assert!(is_incomplete("} {"));
+		assert!(!is_incomplete("} {0|;}"));
+	}
+
+	#[test]
+	fn open_comment() {
+		assert!(is_incomplete("0+; /* unfinished"));
+	}
+}
+
+#[cfg(test)]
+mod errors {
+	use super::parse;
+
+	fn diagnostics(input: &str) -> Vec<(i32, i32, String)> {
+		parse(input.to_string()).unwrap_err().diagnostics.into_iter()
+			.map(|d| (d.line, d.column, d.message))
+			.collect()
+	}
+
+	#[test]
+	fn unclosed_loop() {
+		assert_eq!(diagnostics("0+;\n{0|;"), vec![
+			(2, 1, "unclosed `{`".to_string())
+		]);
+	}
+
+	#[test]
+	fn unmatched_brace() {
+		assert_eq!(diagnostics("0+;}"), vec![
+			(1, 4, "unmatched `}`".to_string())
+		]);
+	}
+
+	#[test]
+	fn missing_pipe() {
+		assert_eq!(diagnostics("0+ {0 }"), vec![
+			(1, 4, "loop guard is missing a `|`".to_string())
+		]);
+		assert_eq!(diagnostics("{0"), vec![
+			(1, 1, "loop guard is missing a `|`".to_string())
+		]);
+	}
+
+	#[test]
+	fn empty_guard() {
+		assert_eq!(diagnostics("{|;}"), vec![
+			(1, 2, "empty loop guard".to_string())
+		]);
+		assert_eq!(diagnostics("{0,!|;}"), vec![
+			(1, 5, "empty loop guard".to_string())
+		]);
+	}
+
+	#[test]
+	fn invalid_guard() {
+		assert_eq!(diagnostics("{0,\n  a1|;}"), vec![
+			(2, 3, "unexpected `a` in loop guard".to_string())
+		]);
+		assert_eq!(diagnostics("{0!|;}"), vec![
+			(1, 3, "unexpected `!` in loop guard".to_string())
+		]);
+	}
+
+	#[test]
+	fn missing_index() {
+		assert_eq!(diagnostics("0+ +;"), vec![
+			(1, 4, "pool `+` has no index".to_string())
+		]);
+	}
+
+	#[test]
+	fn non_ascii_digit() {
+		assert_eq!(diagnostics("\u{663}o;"), vec![
+			(1, 1, "pool `\u{663}` has no index".to_string()),
+			(1, 2, "pool `o` has no index".to_string())
+		]);
+	}
+
+	#[test]
+	fn invalid_declaration() {
+		assert_eq!(diagnostics("@1 = +2"), vec![
+			(1, 2, "`1` cannot name a pool".to_string())
+		]);
+		assert_eq!(diagnostics("@d +2"), vec![
+			(1, 4, "expected `=` after `@d`".to_string())
+		]);
+		assert_eq!(diagnostics("@d = +"), vec![
+			(1, 6, "`+` needs a number".to_string())
+		]);
+		assert_eq!(diagnostics("@d = %"), vec![
+			(1, 6, "unknown operation `%`, expected `+n`, `-n`, `*n`, `=n`, `!` or `.`".to_string())
+		]);
+		assert_eq!(diagnostics("{0| @d = +1 }"), vec![
+			(1, 5, "pools cannot be declared inside a loop".to_string())
+		]);
+	}
+
+	#[test]
+	fn missing_pool() {
+		assert_eq!(diagnostics("0+ 12;"), vec![
+			(1, 4, "index `12` is not followed by a pool".to_string())
+		]);
+		assert_eq!(diagnostics("0+;\n  7"), vec![
+			(2, 3, "index `7` is not followed by a pool".to_string())
+		]);
+	}
+
+	#[test]
+	fn multiple() {
+		assert_eq!(diagnostics("+; {|\n /* ;}"), vec![
+			(1, 1, "pool `+` has no index".to_string()),
+			(1, 5, "empty loop guard".to_string()),
+			(2, 2, "unclosed block comment".to_string()),
+			(1, 4, "unclosed `{`".to_string())
+		]);
+	}
+}
@@ -1,19 +1,31 @@
+use crate::lagoon::{operation::Builtin, span::Span};
+
 #[derive(Debug, PartialEq)]
 pub struct Symbol {
 	pub text: String,
-	pub line: i32
+	pub span: Span
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Guard {
 	pub text: String,
-	pub negated: bool
+	pub negated: bool,
+	pub span: Span
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Loop {
 	pub guard: Vec<Guard>,
-	pub tree: Tree
+	pub tree: Tree,
+	pub span: Span
+}
+
+/// `@<pool> = <operation>`, which brings a new pool into the program
+#[derive(Debug, PartialEq, Clone)]
+pub struct Declaration {
+	pub pool: char,
+	pub operation: Builtin,
+	pub span: Span
 }
 
 #[derive(Debug, PartialEq)]
@@ -30,29 +42,35 @@ impl Tree {
 #[derive(Debug, PartialEq)]
 pub enum Node {
 	Symbol(Symbol),
-	Loop(Loop)
+	Loop(Loop),
+	Declaration(Declaration)
 }
 
 pub mod factory {
-	use super::{Guard, Loop, Node, Symbol, Tree};
+	use crate::lagoon::{operation::Builtin, span::Span};
+	use super::{Declaration, Guard, Loop, Node, Symbol, Tree};
 
 	pub fn new_tree(nodes: Vec<Node>) -> Tree {
 		Tree::new(nodes)
 	}
 
-	pub fn new_symbol(text: &str, line: i32) -> Node {
-		Node::Symbol(Symbol {text: text.to_string(), line})
+	pub fn new_symbol(text: &str, span: Span) -> Node {
+		Node::Symbol(Symbol {text: text.to_string(), span})
+	}
+
+	pub fn new_loop(guard: Vec<Guard>, tree: Tree, span: Span) -> Node {
+		Node::Loop(Loop {guard, tree, span})
 	}
 
-	pub fn new_loop(guard: Vec<Guard>, tree: Tree) -> Node {
-		Node::Loop(Loop {guard, tree})
+	pub fn new_guard(text: &str, negated: bool, span: Span) -> Guard {
+		Guard {text: text.to_string(), negated, span}
 	}
 
-	pub fn new_guard(text: &str, negated: bool) -> Guard {
-		Guard {text: text.to_string(), negated}
+	pub fn new_exec(span: Span) -> Node {
+		Node::Symbol(Symbol {text: ";".to_string(), span})
 	}
 
-	pub fn new_exec(line: i32) -> Node {
-		Node::Symbol(Symbol {text: ";".to_string(), line})
+	pub fn new_declaration(pool: char, operation: Builtin, span: Span) -> Node {
+		Node::Declaration(Declaration {pool, operation, span})
 	}
 }
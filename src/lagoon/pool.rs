@@ -1,13 +1,130 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap};
 use std::hash::Hash;
+use std::{io, mem};
+
+use super::cell::{Cell, Overflow};
+use super::index::Index;
+use super::io::Io;
+
+/// Why a pool could not run over one of its indices. The executor turns this
+/// into a `RuntimeError` that says where it happened.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Fault {
+	Overflow,
+	InputExhausted,
+	Io(String),
+	/// An `Edit` named a pool that does not exist
+	UnknownPool(char)
+}
+
+impl From<io::Error> for Fault {
+	fn from(e: io::Error) -> Self { Fault::Io(e.to_string()) }
+}
+
+/// A change to which indices are in which pool. Pools queue these through
+/// their `Context`, and they are applied in order once every pool has run.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Edit {
+	/// Moves every index in the pool `by` cells, dropping any that would fall
+	/// outside of the index type
+	Shift { pool: char, by: i32 },
+	/// Swaps the indices of two pools
+	Rotate(char, char),
+	/// Removes every index from the pool
+	Clear(char)
+}
 
-pub struct Pool<T: Eq + Hash> {
-	pub func: fn(&mut i32),
-	pub set: HashSet<T>
+/// Read access to memory, however it happens to be stored.
+pub trait Memory<T, C> {
+	fn get(&self, index: &T) -> Option<&C>;
+}
+
+impl<T: Eq + Hash, C> Memory<T, C> for HashMap<T, C> {
+	fn get(&self, index: &T) -> Option<&C> { HashMap::get(self, index) }
+}
+
+/// What a pool is handed along with the cell it is running over.
+pub struct Context<'a, T, C = i32> {
+	/// The index of the cell
+	pub index: &'a T,
+	/// All of memory, as the pools before this one left it. The cell being
+	/// run over is only written through the `datum` handed to the pool.
+	pub memory: &'a dyn Memory<T, C>,
+	pub io: &'a mut Io,
+	/// Membership changes to make once the `;` is over
+	pub edits: &'a mut Vec<Edit>,
+	/// What arithmetic on a cell does when it goes out of range
+	pub overflow: Overflow
+}
+
+impl<T: Eq + Hash, C: Cell> Context<'_, T, C> {
+	/// The value of the cell at `index`.
+	pub fn read(&self, index: &T) -> C {
+		self.memory.get(index).cloned().unwrap_or_default()
+	}
+
+	/// `datum` plus `n`, following `overflow`.
+	pub fn add(&self, datum: &C, n: i64) -> Result<C, Fault> {
+		let result = if n >= 0 {
+			C::from_i64(n, self.overflow).and_then(|n| datum.add(&n, self.overflow))
+		} else {
+			C::from_i64(n.wrapping_neg(), self.overflow).and_then(|n| datum.sub(&n, self.overflow))
+		};
+		result.ok_or(Fault::Overflow)
+	}
+
+	/// `datum` times `n`, following `overflow`.
+	pub fn mul(&self, datum: &C, n: i64) -> Result<C, Fault> {
+		C::from_i64(n, self.overflow)
+			.and_then(|n| datum.mul(&n, self.overflow))
+			.ok_or(Fault::Overflow)
+	}
+
+	/// `n` as a cell, following `overflow`.
+	pub fn constant(&self, n: i64) -> Result<C, Fault> {
+		C::from_i64(n, self.overflow).ok_or(Fault::Overflow)
+	}
 }
 
-impl<T: Eq + Hash> Pool<T> {
-	pub fn new(func: fn(&mut i32)) -> Self { Self { func, set: HashSet::new() } }
+/// What a pool does to each of its cells on `;`. Any closure over the cell and
+/// its `Context` is an operation, so it can capture configuration or keep
+/// state between calls.
+pub trait Operation<T, C = i32> {
+	fn apply(&mut self, datum: &mut C, context: &mut Context<T, C>) -> Result<(), Fault>;
+
+	/// What the operation adds to every cell it runs over, if that is all it
+	/// ever does. Loops over such pools can then be run in one go.
+	fn delta(&self) -> Option<i64> { None }
+}
+
+impl<T, C, F> Operation<T, C> for F
+where
+	F: FnMut(&mut C, &mut Context<T, C>) -> Result<(), Fault>
+{
+	fn apply(&mut self, datum: &mut C, context: &mut Context<T, C>) -> Result<(), Fault> {
+		self(datum, context)
+	}
+}
+
+pub struct Pool<T: Eq + Hash + Ord, C = i32> {
+	pub func: Box<dyn Operation<T, C>>,
+	/// Pools with a lower priority run first on `;`
+	pub priority: i32,
+	/// The indices in the pool, which `func` runs over in ascending order
+	pub set: BTreeSet<T>
+}
+
+impl<T: Eq + Hash + Ord, C> Pool<T, C> {
+	pub fn new<F>(func: F) -> Self
+	where
+		F: FnMut(&mut C, &mut Context<T, C>) -> Result<(), Fault> + 'static
+	{
+		Self::from_operation(func)
+	}
+
+	pub fn from_operation(operation: impl Operation<T, C> + 'static) -> Self {
+		Self { func: Box::new(operation), priority: 0, set: BTreeSet::new() }
+	}
 
 	pub fn add(&mut self, item: T) -> bool {
 		let success = self.set.contains(&item);
@@ -34,75 +151,170 @@ impl<T: Eq + Hash> Pool<T> {
 		}
 		return !exists;
 	}
+}
 
-	pub fn execute(self, memory: &mut HashMap<T, i32>) {
-		for i in self.set { (self.func)((*memory).entry(i).or_insert(0)); }
+impl<T: Eq + Hash + Ord + Clone, C: Cell> Pool<T, C> {
+	/// Runs the pool over each of its indices, returning the edits it queued
+	/// for whoever holds the pools they name.
+	pub fn execute(
+		&mut self,
+		memory: &mut HashMap<T, C>,
+		io: &mut Io,
+		overflow: Overflow
+	) -> Result<Vec<Edit>, Fault> {
+		let mut edits = Vec::new();
+		for i in self.set.iter() {
+			let mut datum = memory.get(i).cloned().unwrap_or_default();
+			let mut context = Context { index: i, memory, io, edits: &mut edits, overflow };
+			let result = self.func.apply(&mut datum, &mut context);
+			memory.insert(i.clone(), datum);
+			result?;
+		}
+		Ok(edits)
 	}
 }
 
-pub struct Pools<T: Eq + Hash> {
+pub struct Pools<T: Eq + Hash + Ord, C = i32> {
 	pub count: i32,
-	pub map: HashMap<char, Pool<T>>
+	pub map: HashMap<char, Pool<T, C>>,
+	/// The order the pools run in on `;`: by priority, then by when they were
+	/// added
+	pub order: Vec<char>,
+	registered: Vec<char>,
+	pub io: Io
 }
 
-impl<T: Eq + Hash> Pools<T> {
-	pub fn new() -> Self { Self { count: 0, map: HashMap::new() } }
+impl<T: Eq + Hash + Ord, C: 'static> Pools<T, C> {
+	pub fn new() -> Self {
+		Self {
+			count: 0,
+			map: HashMap::new(),
+			order: Vec::new(),
+			registered: Vec::new(),
+			io: Io::stdio()
+		}
+	}
 
 	/// ```rust
-	/// add_pool(&mut self, c: char, func: fn(&mut T))
+	/// add_pool(&mut self, c: char, func: impl FnMut(&mut C, &mut Context<T, C>) -> Result<(), Fault>)
 	/// ```
 	///
 	/// Adds a new pool to the program that runs `func` over each memory slot contained in it when an execute command runs.
+	/// `func` is also handed a `Context` with the slot's index and the pools' `io`, for pools that read or write.
 	///
 	/// # Examples
 	///
-	/// ```rust
-	/// use lagoon::Lagoon;
-	///
-	/// let mut lagoon = Lagoon::new();
-	/// lagoon.pools.add_pool('i', |index| *index += 1);
+	/// ```ignore
+	/// let mut lagoon: Lagoon<i32, i32> = Lagoon::new();
+	/// let step = 5;
+	/// lagoon.pools.add_pool('f', move |datum, _| { *datum += step; Ok(()) });
 	/// ```
-	///
-	/// temp
-	pub fn add_pool(
+	pub fn add_pool<F>(&mut self, identifier: char, func: F) -> &mut Self
+	where
+		F: FnMut(&mut C, &mut Context<T, C>) -> Result<(), Fault> + 'static
+	{
+		self.add_operation(identifier, func)
+	}
+
+	/// Adds a pool that queues `edit` once for each of its indices on `;`, so
+	/// a single index makes it fire once per `;`.
+	pub fn add_edit(&mut self, identifier: char, edit: Edit) -> &mut Self {
+		self.add_pool(identifier, move |_, context| {
+			context.edits.push(edit);
+			Ok(())
+		})
+	}
+
+	/// Like `add_pool`, for an `Operation` that is not a closure.
+	pub fn add_operation(
 		&mut self,
 		identifier: char,
-		func: fn(&mut i32)
+		operation: impl Operation<T, C> + 'static
 	) -> &mut Self {
 		// if self.map.contains_key(&identifier) { /* Error */ }
-		self.map.insert(identifier, Pool::new(func));
+		if self.map.insert(identifier, Pool::from_operation(operation)).is_none() {
+			self.registered.push(identifier);
+		}
+		self.sort();
 		return self;
 	}
 
-	pub fn get_pool(
-		&mut self,
-		identifier: char
-	) -> Result<&mut Pool<T>, &'static str> {
-		match self.map.get_mut(&identifier) {
-			Some(p) => Ok(p),
-			None => Err("invalid")
+	pub fn get_pool(&mut self, identifier: char) -> Option<&mut Pool<T, C>> {
+		self.map.get_mut(&identifier)
+	}
+
+	/// Moves a pool ahead of (lower) or behind (higher) the others on `;`.
+	/// Every pool starts out with a priority of `0`.
+	pub fn set_priority(&mut self, identifier: char, priority: i32) -> &mut Self {
+		if let Some(pool) = self.map.get_mut(&identifier) {
+			pool.priority = priority;
 		}
+		self.sort();
+		return self;
 	}
 
-	pub fn execute(self, memory: &mut HashMap<T, i32>) {
-		for (_, pool) in self.map {
-			pool.execute(memory);
+	fn sort(&mut self) {
+		let map = &self.map;
+		self.order = self.registered.clone();
+		self.order.sort_by_key(|c| map[c].priority);
+	}
+}
+
+impl<T: Index, C> Pools<T, C> {
+	/// Makes the membership change `edit` describes, or returns the pool it
+	/// names that does not exist.
+	pub fn edit(&mut self, edit: Edit) -> Result<(), char> {
+		let missing = |pools: &Self, c: char| if pools.map.contains_key(&c) { Ok(()) } else { Err(c) };
+		match edit {
+			Edit::Shift { pool, by } => {
+				missing(self, pool)?;
+				let pool = self.map.get_mut(&pool).unwrap();
+				pool.set = pool.set.iter().filter_map(|i| i.offset(by)).collect();
+			},
+			Edit::Rotate(a, b) => {
+				missing(self, a)?;
+				missing(self, b)?;
+				if a == b { return Ok(()); }
+				let first = mem::take(&mut self.map.get_mut(&a).unwrap().set);
+				let second = mem::replace(&mut self.map.get_mut(&b).unwrap().set, first);
+				self.map.get_mut(&a).unwrap().set = second;
+			},
+			Edit::Clear(pool) => {
+				missing(self, pool)?;
+				self.map.get_mut(&pool).unwrap().set.clear();
+			}
 		}
+		Ok(())
+	}
+}
+
+impl<T: Index, C: Cell> Pools<T, C> {
+	/// Runs every pool in order, then makes the edits they queued.
+	pub fn execute(&mut self, memory: &mut HashMap<T, C>, overflow: Overflow) -> Result<(), Fault> {
+		let mut edits = Vec::new();
+		for c in self.order.iter() {
+			let pool = self.map.get_mut(c).unwrap();
+			edits.extend(pool.execute(memory, &mut self.io, overflow)?);
+		}
+		for edit in edits {
+			self.edit(edit).map_err(Fault::UnknownPool)?;
+		}
+		Ok(())
 	}
 }
 
 #[cfg(test)]
 mod single_pool {
 	use std::collections::HashMap;
-	use crate::lagoon::pool::Pool;
+	use crate::lagoon::{cell::Overflow, io::Io, pool::Pool};
 
 	#[test]
 	fn single_index() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
-		let mut pool: Pool<i32> = Pool::new(|datum| *datum += 1);
+		let mut pool: Pool<i32> = Pool::new(|datum, _| { *datum += 1; Ok(()) });
 
 		pool.add(0);
-		pool.execute(&mut memory);
+		pool.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
 
 		assert_eq!(*memory.get(&0).unwrap_or(&0), 1);
 	}
@@ -110,11 +322,11 @@ mod single_pool {
 	#[test]
 	fn multiple_indices() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
-		let mut pool: Pool<i32> = Pool::new(|datum| *datum += 1);
+		let mut pool: Pool<i32> = Pool::new(|datum, _| { *datum += 1; Ok(()) });
 
 		pool.add(0);
 		pool.add(2);
-		pool.execute(&mut memory);
+		pool.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
 
 		assert_eq!(*memory.get(&0).unwrap_or(&0), 1);
 		assert_eq!(*memory.get(&1).unwrap_or(&0), 0);
@@ -124,11 +336,11 @@ mod single_pool {
 	#[test]
 	fn double_toggle() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
-		let mut pool: Pool<i32> = Pool::new(|datum| *datum += 1);
+		let mut pool: Pool<i32> = Pool::new(|datum, _| { *datum += 1; Ok(()) });
 
 		pool.toggle(0);
 		pool.toggle(0);
-		pool.execute(&mut memory);
+		pool.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
 
 		assert_eq!(*memory.get(&0).unwrap_or(&0), 0);
 	}
@@ -137,18 +349,18 @@ mod single_pool {
 #[cfg(test)]
 mod multi_pool {
 	use std::collections::HashMap;
-	use crate::lagoon::pool::Pool;
+	use crate::lagoon::{cell::Overflow, io::Io, pool::Pool};
 
 	#[test]
 	fn single_index_each() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
-		let mut pool_a: Pool<i32> = Pool::new(|datum| *datum += 1);
-		let mut pool_b: Pool<i32> = Pool::new(|datum| *datum += 1);
+		let mut pool_a: Pool<i32> = Pool::new(|datum, _| { *datum += 1; Ok(()) });
+		let mut pool_b: Pool<i32> = Pool::new(|datum, _| { *datum += 1; Ok(()) });
 
 		pool_a.add(0);
 		pool_b.add(1);
-		pool_a.execute(&mut memory);
-		pool_b.execute(&mut memory);
+		pool_a.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
+		pool_b.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
 
 		assert_eq!(*memory.get(&0).unwrap_or(&0), 1);
 		assert_eq!(*memory.get(&1).unwrap_or(&0), 1);
@@ -157,13 +369,13 @@ mod multi_pool {
 	#[test]
 	fn single_index_overlap() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
-		let mut pool_a: Pool<i32> = Pool::new(|datum| *datum += 1);
-		let mut pool_b: Pool<i32> = Pool::new(|datum| *datum += 1);
+		let mut pool_a: Pool<i32> = Pool::new(|datum, _| { *datum += 1; Ok(()) });
+		let mut pool_b: Pool<i32> = Pool::new(|datum, _| { *datum += 1; Ok(()) });
 
 		pool_a.add(0);
 		pool_b.add(0);
-		pool_a.execute(&mut memory);
-		pool_b.execute(&mut memory);
+		pool_a.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
+		pool_b.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
 
 		assert_eq!(*memory.get(&0).unwrap_or(&0), 2);
 	}
@@ -171,18 +383,258 @@ mod multi_pool {
 	#[test]
 	fn multiple_indices() {
 		let mut memory: HashMap<i32, i32> = HashMap::new();
-		let mut pool_a: Pool<i32> = Pool::new(|datum| *datum += 1);
-		let mut pool_b: Pool<i32> = Pool::new(|datum| *datum -= 1);
+		let mut pool_a: Pool<i32> = Pool::new(|datum, _| { *datum += 1; Ok(()) });
+		let mut pool_b: Pool<i32> = Pool::new(|datum, _| { *datum -= 1; Ok(()) });
 
 		pool_a.add(0);
 		pool_a.add(1);
 		pool_b.add(1);
 		pool_b.add(2);
-		pool_a.execute(&mut memory);
-		pool_b.execute(&mut memory);
+		pool_a.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
+		pool_b.execute(&mut memory, &mut Io::memory("").0, Overflow::default()).unwrap();
 
 		assert_eq!(*memory.get(&0).unwrap_or(&0), 1);
 		assert_eq!(*memory.get(&1).unwrap_or(&0), 0);
 		assert_eq!(*memory.get(&2).unwrap_or(&0), -1);
 	}
 }
+
+#[cfg(test)]
+mod input_pool {
+	use std::collections::HashMap;
+	use crate::lagoon::{cell::Overflow, io::Io, pool::{Fault, Pool}};
+
+	#[test]
+	fn reads_each_index() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let (mut io, _) = Io::memory("3 4");
+		let mut pool: Pool<i32> = Pool::new(|datum, context| {
+			*datum = context.io.input.read()?.ok_or(Fault::InputExhausted)?;
+			Ok(())
+		});
+
+		memory.insert(0, 7);
+		pool.add(0);
+		pool.execute(&mut memory, &mut io, Overflow::default()).unwrap();
+
+		assert_eq!(*memory.get(&0).unwrap_or(&0), 3);
+	}
+
+	#[test]
+	fn exhausted() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let (mut io, _) = Io::memory("");
+		let mut pool: Pool<i32> = Pool::new(|datum, context| {
+			*datum = context.io.input.read()?.ok_or(Fault::InputExhausted)?;
+			Ok(())
+		});
+
+		pool.add(0);
+		assert_eq!(pool.execute(&mut memory, &mut io, Overflow::default()), Err(Fault::InputExhausted));
+	}
+}
+
+#[cfg(test)]
+mod order {
+	use std::collections::HashMap;
+	use crate::lagoon::{cell::Overflow, io::Io, pool::Pools};
+
+	fn pools() -> Pools<i32> {
+		let mut pools: Pools<i32> = Pools::new();
+		pools.add_pool('d', |datum, _| { *datum *= 2; Ok(()) });
+		pools.add_pool('+', |datum, _| { *datum += 1; Ok(()) });
+		pools
+	}
+
+	#[test]
+	fn registration() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools = pools();
+		pools.get_pool('d').unwrap().add(0);
+		pools.get_pool('+').unwrap().add(0);
+
+		assert_eq!(pools.order, vec!['d', '+']);
+		pools.execute(&mut memory, Overflow::default()).unwrap();
+		assert_eq!(*memory.get(&0).unwrap_or(&0), 1);
+	}
+
+	#[test]
+	fn priority() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools = pools();
+		pools.set_priority('+', -1);
+		pools.get_pool('d').unwrap().add(0);
+		pools.get_pool('+').unwrap().add(0);
+
+		assert_eq!(pools.order, vec!['+', 'd']);
+		pools.execute(&mut memory, Overflow::default()).unwrap();
+		assert_eq!(*memory.get(&0).unwrap_or(&0), 2);
+	}
+
+	#[test]
+	fn replaced_pool_keeps_its_place() {
+		let mut pools = pools();
+		pools.add_pool('d', |datum, _| { *datum *= 3; Ok(()) });
+		assert_eq!(pools.order, vec!['d', '+']);
+	}
+
+	#[test]
+	fn ascending_indices() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools: Pools<i32> = Pools::new();
+		let (io, output) = Io::memory("");
+		pools.io = io;
+		pools.add_pool('o', |datum, context| Ok(context.io.output.write(datum)?));
+		for i in [3, -1, 10, 0] {
+			memory.insert(i, i);
+			pools.get_pool('o').unwrap().add(i);
+		}
+
+		pools.execute(&mut memory, Overflow::default()).unwrap();
+		assert_eq!(output.contents(), "-1 0 3 10");
+	}
+}
+
+#[cfg(test)]
+mod operation {
+	use std::collections::HashMap;
+	use crate::lagoon::cell::Overflow;
+	use crate::lagoon::pool::{Context, Fault, Operation, Pools};
+
+	struct Step { by: i32 }
+
+	impl Operation<i32> for Step {
+		fn apply(&mut self, datum: &mut i32, _: &mut Context<i32>) -> Result<(), Fault> {
+			*datum += self.by;
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn captured_state() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools: Pools<i32> = Pools::new();
+		let mut calls = 0;
+		pools.add_pool('c', move |datum, _| { calls += 1; *datum = calls; Ok(()) });
+		for i in [0, 1, 2] { pools.get_pool('c').unwrap().add(i); }
+
+		pools.execute(&mut memory, Overflow::default()).unwrap();
+		assert_eq!(*memory.get(&2).unwrap_or(&0), 3);
+	}
+
+	#[test]
+	fn index() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools: Pools<i32> = Pools::new();
+		pools.add_pool('n', |datum, context| { *datum = *context.index * 10; Ok(()) });
+		pools.get_pool('n').unwrap().add(4);
+
+		pools.execute(&mut memory, Overflow::default()).unwrap();
+		assert_eq!(*memory.get(&4).unwrap_or(&0), 40);
+	}
+
+	#[test]
+	fn reads_memory() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools: Pools<i32> = Pools::new();
+		pools.add_pool('s', |datum, context| { *datum += context.read(&10); Ok(()) });
+		memory.insert(10, 7);
+		pools.get_pool('s').unwrap().add(0);
+		pools.get_pool('s').unwrap().add(10);
+
+		pools.execute(&mut memory, Overflow::default()).unwrap();
+		assert_eq!(*memory.get(&0).unwrap_or(&0), 7);
+		assert_eq!(*memory.get(&10).unwrap_or(&0), 14);
+	}
+
+	#[test]
+	fn trait_object() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools: Pools<i32> = Pools::new();
+		pools.add_operation('s', Step { by: 5 });
+		pools.get_pool('s').unwrap().add(0);
+		pools.get_pool('s').unwrap().add(1);
+
+		pools.execute(&mut memory, Overflow::default()).unwrap();
+		assert_eq!(*memory.get(&0).unwrap_or(&0), 5);
+		assert_eq!(*memory.get(&1).unwrap_or(&0), 5);
+	}
+
+	#[test]
+	fn overflow() {
+		let mut memory: HashMap<i32, u8> = vec![(0, 255)].into_iter().collect();
+		let mut pools: Pools<i32, u8> = Pools::new();
+		pools.add_pool('+', |datum, context| { *datum = context.add(datum, 1)?; Ok(()) });
+		pools.get_pool('+').unwrap().add(0);
+
+		assert_eq!(pools.execute(&mut memory, Overflow::Check), Err(Fault::Overflow));
+		pools.execute(&mut memory, Overflow::Wrap).unwrap();
+		assert_eq!(memory[&0], 0);
+	}
+}
+
+#[cfg(test)]
+mod edit {
+	use std::collections::HashMap;
+	use crate::lagoon::cell::Overflow;
+	use crate::lagoon::pool::{Edit, Fault, Pools};
+
+	fn pools() -> Pools<i32> {
+		let mut pools: Pools<i32> = Pools::new();
+		pools.add_pool('a', |_, _| Ok(()));
+		pools.add_pool('b', |_, _| Ok(()));
+		for i in [0, 2] { pools.get_pool('a').unwrap().add(i); }
+		pools.get_pool('b').unwrap().add(7);
+		pools
+	}
+
+	fn indices(pools: &mut Pools<i32>, c: char) -> Vec<i32> {
+		pools.get_pool(c).unwrap().set.iter().copied().collect()
+	}
+
+	#[test]
+	fn shift() {
+		let mut pools = pools();
+		pools.edit(Edit::Shift { pool: 'a', by: -1 }).unwrap();
+		assert_eq!(indices(&mut pools, 'a'), vec![-1, 1]);
+
+		let mut narrow: Pools<u8> = Pools::new();
+		narrow.add_pool('a', |_, _| Ok(()));
+		narrow.get_pool('a').unwrap().add(0);
+		narrow.get_pool('a').unwrap().add(3);
+		narrow.edit(Edit::Shift { pool: 'a', by: -1 }).unwrap();
+		assert!(narrow.get_pool('a').unwrap().set.iter().eq(&[2]));
+	}
+
+	#[test]
+	fn rotate() {
+		let mut pools = pools();
+		pools.edit(Edit::Rotate('a', 'b')).unwrap();
+		assert_eq!(indices(&mut pools, 'a'), vec![7]);
+		assert_eq!(indices(&mut pools, 'b'), vec![0, 2]);
+		pools.edit(Edit::Rotate('a', 'a')).unwrap();
+		assert_eq!(indices(&mut pools, 'a'), vec![7]);
+	}
+
+	#[test]
+	fn clear() {
+		let mut pools = pools();
+		pools.edit(Edit::Clear('a')).unwrap();
+		assert!(indices(&mut pools, 'a').is_empty());
+		assert_eq!(pools.edit(Edit::Clear('z')), Err('z'));
+	}
+
+	#[test]
+	fn queued() {
+		let mut memory: HashMap<i32, i32> = HashMap::new();
+		let mut pools = pools();
+		pools.add_edit('s', Edit::Shift { pool: 'a', by: 1 });
+		pools.get_pool('s').unwrap().add(0);
+		pools.execute(&mut memory, Overflow::default()).unwrap();
+		assert_eq!(indices(&mut pools, 'a'), vec![1, 3]);
+
+		pools.add_edit('x', Edit::Clear('z'));
+		pools.get_pool('x').unwrap().add(0);
+		assert_eq!(pools.execute(&mut memory, Overflow::default()), Err(Fault::UnknownPool('z')));
+	}
+}
@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::executor::observer::Observer;
+use super::span::Span;
+
+/// How often a `;` ran, and how many cells each pool changed while it did.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Runs {
+	pub runs: usize,
+	pub updates: BTreeMap<char, usize>
+}
+
+/// An `Observer` that counts where a run spends its time. Toggles, `;` and
+/// guard checks each count as one hit, on the line they are on and under the
+/// loops they are nested in.
+#[derive(Debug, Default)]
+pub struct Profiler {
+	/// Hits on each line
+	pub lines: BTreeMap<i32, usize>,
+	/// How many times each loop went around
+	pub loops: BTreeMap<Span, usize>,
+	pub execs: BTreeMap<Span, Runs>,
+	/// Hits under each nesting of loops, innermost last
+	stacks: HashMap<Vec<Span>, usize>,
+	/// The loops running right now
+	frames: Vec<Span>,
+	/// Cells changed by the `;` running right now, by pool
+	updates: BTreeMap<char, usize>
+}
+
+impl Profiler {
+	pub fn new() -> Self { Self::default() }
+
+	/// `source` with the hits on each line beside it, then every loop by how
+	/// many times it went around and every `;` by how many times it ran.
+	pub fn listing(&self, source: &str) -> String {
+		let mut out = String::new();
+		for (n, line) in source.lines().enumerate() {
+			let hits = self.lines.get(&(n as i32 + 1)).map(|h| h.to_string()).unwrap_or_default();
+			out.push_str(&format!("{:>10} | {}\n", hits, line));
+		}
+		let mut loops: Vec<_> = self.loops.iter().collect();
+		loops.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+		for (span, iterations) in loops {
+			out.push_str(&format!("{:>10} iterations of the loop at {}\n", iterations, span));
+		}
+		let mut execs: Vec<_> = self.execs.iter().collect();
+		execs.sort_by(|a, b| b.1.runs.cmp(&a.1.runs).then(a.0.cmp(b.0)));
+		for (span, runs) in execs {
+			out.push_str(&format!("{:>10} runs of the `;` at {}", runs.runs, span));
+			let updates: Vec<String> = runs.updates.iter().map(|(pool, n)| format!("{} {}", pool, n)).collect();
+			if !updates.is_empty() {
+				out.push_str(&format!(", changing cells in {}", updates.join(", ")));
+			}
+			out.push('\n');
+		}
+		out
+	}
+
+	/// The hits under each nesting of loops as folded stacks, one per line,
+	/// which flamegraph tools take as they are. Every stack starts at `root`,
+	/// with any `;` or whitespace in it turned into `_`, since those tools
+	/// split frames on the one and the count off on the other.
+	pub fn folded(&self, root: &str) -> String {
+		let root: String = root.chars().map(|c| if c == ';' || c.is_whitespace() { '_' } else { c }).collect();
+		let mut lines: Vec<String> = self.stacks.iter().map(|(frames, hits)| {
+			let mut line = root.clone();
+			for span in frames { line.push_str(&format!(";loop@{}", span)); }
+			format!("{} {}\n", line, hits)
+		}).collect();
+		lines.sort();
+		lines.concat()
+	}
+
+	/// Counts a run of the `;` at `span`, and the cells it changed.
+	fn ran(&mut self, span: Span) {
+		self.hit(span);
+		let runs = self.execs.entry(span).or_default();
+		runs.runs += 1;
+		for (pool, n) in std::mem::take(&mut self.updates) {
+			*runs.updates.entry(pool).or_insert(0) += n;
+		}
+	}
+
+	fn hit(&mut self, span: Span) {
+		*self.lines.entry(span.line).or_insert(0) += 1;
+		match self.stacks.get_mut(self.frames.as_slice()) {
+			Some(hits) => *hits += 1,
+			None => { self.stacks.insert(self.frames.clone(), 1); }
+		}
+	}
+}
+
+impl<T, C: PartialEq> Observer<T, C> for Profiler {
+	fn toggle(&mut self, _pool: char, _index: &T, span: Span) { self.hit(span); }
+
+	fn update(&mut self, pool: char, _index: &T, before: &C, after: &C) {
+		if before != after { *self.updates.entry(pool).or_insert(0) += 1; }
+	}
+
+	fn exec(&mut self, span: Span) { self.ran(span); }
+
+	/// A `;` that failed still ran, as far as it got.
+	fn fail(&mut self, span: Span) { self.ran(span); }
+
+	fn enter(&mut self, span: Span) {
+		self.frames.push(span);
+		self.loops.entry(span).or_insert(0);
+	}
+
+	fn guard(&mut self, span: Span, holds: bool) {
+		self.hit(span);
+		if holds { *self.loops.entry(span).or_insert(0) += 1; }
+	}
+
+	fn exit(&mut self, _span: Span) { self.frames.pop(); }
+}
+
+#[cfg(test)]
+mod counts {
+	use crate::lagoon::Lagoon;
+	use crate::lagoon::executor::Semantics;
+	use crate::lagoon::io::Io;
+	use crate::lagoon::span::Span;
+	use super::Profiler;
+
+	/// Counts down from 2 in an outer loop, and from 3 in an inner one each
+	/// time around.
+	const NESTED: &str = "0+;;0+\n{0|\n0- 1+;0- ;;1+ 1-\n{1|;}\n1-}";
+
+	fn profile(source: &str) -> Profiler {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.pools.io = Io::memory("").0;
+		lagoon.append_raw(source).unwrap();
+		let mut profiler = Profiler::new();
+		lagoon.run_observed(&mut profiler).unwrap();
+		profiler
+	}
+
+	#[test]
+	fn iterations() {
+		let profiler = profile(NESTED);
+		let outer = profiler.loops.iter().find(|(s, _)| s.line == 2).unwrap();
+		let inner = profiler.loops.iter().find(|(s, _)| s.line == 4).unwrap();
+		assert_eq!(*outer.1, 2);
+		assert_eq!(*inner.1, 6);
+	}
+
+	#[test]
+	fn lines() {
+		let profiler = profile(NESTED);
+		assert_eq!(profiler.lines[&1], 4);
+		// A guard check for each time around, and one more to leave
+		assert_eq!(profiler.lines[&2], 3);
+		assert_eq!(profiler.lines[&3], 2 * 8);
+		assert_eq!(profiler.lines[&4], 2 * 4 + 6);
+		assert_eq!(profiler.lines[&5], 2);
+	}
+
+	#[test]
+	fn updates() {
+		let profiler = profile("0+ 1+ 2o;\n0- 1+;");
+		let first = &profiler.execs[&Span::new(1, 9, 8, 9)];
+		assert_eq!(first.runs, 1);
+		assert_eq!(first.updates, vec![('+', 2)].into_iter().collect());
+		let second = &profiler.execs[&Span::new(2, 6, 15, 16)];
+		assert_eq!(second.updates, vec![('+', 1), ('-', 1)].into_iter().collect());
+	}
+
+	/// Each pool counts the cells it changed, even when the changes of pools
+	/// sharing a cell cancel out.
+	#[test]
+	fn simultaneous() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.pools.io = Io::memory("").0;
+		lagoon.semantics = Semantics::Simultaneous;
+		lagoon.append_raw("0+ 0- 1+;").unwrap();
+		let mut profiler = Profiler::new();
+		lagoon.run_observed(&mut profiler).unwrap();
+		let exec = &profiler.execs[&Span::new(1, 9, 8, 9)];
+		assert_eq!(exec.updates, vec![('+', 2), ('-', 1)].into_iter().collect());
+		assert_eq!(lagoon.memory[&0], 0);
+	}
+
+	#[test]
+	fn listing() {
+		let profiler = profile("0+;0- 0+\n{0|;}");
+		assert_eq!(profiler.listing("0+;0- 0+\n{0|;}\n"), "\
+			\x20        4 | 0+;0- 0+\n\
+			\x20        3 | {0|;}\n\
+			\x20        1 iterations of the loop at 2:1\n\
+			\x20        1 runs of the `;` at 1:3, changing cells in + 1\n\
+			\x20        1 runs of the `;` at 2:4, changing cells in - 1\n"
+		);
+	}
+
+	#[test]
+	fn folded() {
+		let profiler = profile(NESTED);
+		assert_eq!(profiler.folded("nested"), "\
+			nested 4\n\
+			nested;loop@2:1 21\n\
+			nested;loop@2:1;loop@4:1 14\n"
+		);
+	}
+
+	#[test]
+	fn folded_root() {
+		let profiler = profile("0+;");
+		assert_eq!(profiler.folded("my dir/a;b\t.lag"), "my_dir/a_b_.lag 2\n");
+	}
+}
@@ -0,0 +1,33 @@
+use std::fmt;
+
+/// Where something came from in the source: the line and column it starts at
+/// and the byte range it covers.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+pub struct Span {
+	pub line: i32,
+	pub column: i32,
+	pub start: usize,
+	pub end: usize
+}
+
+impl Span {
+	pub fn new(line: i32, column: i32, start: usize, end: usize) -> Self {
+		Self { line, column, start, end }
+	}
+
+	/// A span from the start of this one to the end of `other`.
+	pub fn to(&self, other: Span) -> Span {
+		Span { end: other.end, ..*self }
+	}
+
+	/// The text the span covers in `source`.
+	pub fn slice<'a>(&self, source: &'a str) -> &'a str {
+		&source[self.start..self.end]
+	}
+}
+
+impl fmt::Display for Span {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}", self.line, self.column)
+	}
+}
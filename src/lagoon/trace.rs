@@ -0,0 +1,157 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use super::executor::observer::Observer;
+use super::span::Span;
+
+/// An `Observer` that writes a line of JSON to `out` for every toggle, `;`
+/// and loop a run goes through, along with where it came from. A `;` lists
+/// the cells it changed, and the pool that changed each one.
+pub struct Tracer<W: Write> {
+	out: W,
+	/// The cells changed so far by the current `;`, already as JSON
+	cells: Vec<String>,
+	/// The first write that failed, since observers cannot fail
+	error: Option<io::Error>
+}
+
+impl<W: Write> Tracer<W> {
+	pub fn new(out: W) -> Self {
+		Self { out, cells: Vec::new(), error: None }
+	}
+
+	/// Flushes the trace and hands back `out`, or the first error writing to it.
+	pub fn finish(mut self) -> io::Result<W> {
+		if let Some(e) = self.error.take() { return Err(e); }
+		self.out.flush()?;
+		Ok(self.out)
+	}
+
+	fn record(&mut self, kind: &str, span: Span, rest: &str) {
+		if self.error.is_some() { return; }
+		let result = writeln!(
+			self.out,
+			"{{\"kind\":\"{}\",\"line\":{},\"column\":{}{}}}",
+			kind, span.line, span.column, rest
+		);
+		if let Err(e) = result { self.error = Some(e); }
+	}
+}
+
+/// `c` as a JSON string.
+fn string(c: char) -> String {
+	match c {
+		'"' => "\"\\\"\"".to_string(),
+		'\\' => "\"\\\\\"".to_string(),
+		c if c.is_control() => format!("\"\\u{:04x}\"", c as u32),
+		c => format!("\"{}\"", c)
+	}
+}
+
+impl<W: Write, T: Display, C: Display + PartialEq> Observer<T, C> for Tracer<W> {
+	fn toggle(&mut self, pool: char, index: &T, span: Span) {
+		self.record("toggle", span, &format!(",\"pool\":{},\"index\":{}", string(pool), index));
+	}
+
+	fn update(&mut self, pool: char, index: &T, before: &C, after: &C) {
+		if before == after { return; }
+		self.cells.push(format!(
+			"{{\"pool\":{},\"index\":{},\"from\":{},\"to\":{}}}",
+			string(pool), index, before, after
+		));
+	}
+
+	fn exec(&mut self, span: Span) {
+		let cells = std::mem::take(&mut self.cells);
+		self.record("exec", span, &format!(",\"cells\":[{}]", cells.join(",")));
+	}
+
+	fn fail(&mut self, span: Span) {
+		let cells = std::mem::take(&mut self.cells);
+		self.record("exec-failed", span, &format!(",\"cells\":[{}]", cells.join(",")));
+	}
+
+	fn enter(&mut self, span: Span) { self.record("loop-enter", span, ""); }
+
+	fn guard(&mut self, span: Span, holds: bool) {
+		self.record("guard-check", span, &format!(",\"holds\":{}", holds));
+	}
+
+	fn exit(&mut self, span: Span) { self.record("loop-exit", span, ""); }
+}
+
+#[cfg(test)]
+mod records {
+	use crate::lagoon::Lagoon;
+	use crate::lagoon::executor::Semantics;
+	use crate::lagoon::io::Io;
+	use super::{Tracer, string};
+
+	#[test]
+	fn run() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.pools.io = Io::memory("").0;
+		lagoon.append_raw("0+ 1o;\n0+ 0-\n{0|;}").unwrap();
+		let mut tracer = Tracer::new(Vec::new());
+		lagoon.run_observed(&mut tracer).unwrap();
+		let trace = tracer.finish().unwrap();
+		assert_eq!(String::from_utf8(trace).unwrap(), "\
+			{\"kind\":\"toggle\",\"line\":1,\"column\":1,\"pool\":\"+\",\"index\":0}\n\
+			{\"kind\":\"toggle\",\"line\":1,\"column\":4,\"pool\":\"o\",\"index\":1}\n\
+			{\"kind\":\"exec\",\"line\":1,\"column\":6,\"cells\":[{\"pool\":\"+\",\"index\":0,\"from\":0,\"to\":1}]}\n\
+			{\"kind\":\"toggle\",\"line\":2,\"column\":1,\"pool\":\"+\",\"index\":0}\n\
+			{\"kind\":\"toggle\",\"line\":2,\"column\":4,\"pool\":\"-\",\"index\":0}\n\
+			{\"kind\":\"loop-enter\",\"line\":3,\"column\":1}\n\
+			{\"kind\":\"guard-check\",\"line\":3,\"column\":1,\"holds\":true}\n\
+			{\"kind\":\"exec\",\"line\":3,\"column\":4,\"cells\":[{\"pool\":\"-\",\"index\":0,\"from\":1,\"to\":0}]}\n\
+			{\"kind\":\"guard-check\",\"line\":3,\"column\":1,\"holds\":false}\n\
+			{\"kind\":\"loop-exit\",\"line\":3,\"column\":1}\n"
+		);
+	}
+
+	#[test]
+	fn simultaneous() {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.pools.io = Io::memory("").0;
+		lagoon.semantics = Semantics::Simultaneous;
+		lagoon.append_raw("0+ 0-;").unwrap();
+		let mut tracer = Tracer::new(Vec::new());
+		lagoon.run_observed(&mut tracer).unwrap();
+		let trace = String::from_utf8(tracer.finish().unwrap()).unwrap();
+		assert_eq!(trace.lines().last().unwrap(), "{\"kind\":\"exec\",\"line\":1,\"column\":6,\"cells\":[\
+			{\"pool\":\"+\",\"index\":0,\"from\":0,\"to\":1},\
+			{\"pool\":\"-\",\"index\":0,\"from\":0,\"to\":-1}]}"
+		);
+		assert_eq!(lagoon.memory[&0], 0);
+	}
+
+	/// The cells a failed `;` changed are written down with it, and not with
+	/// whatever comes next.
+	#[test]
+	fn failed() {
+		let mut tracer = Tracer::new(Vec::new());
+		for source in ["0+ 1i;", "2+;"].iter() {
+			let mut lagoon: Lagoon<i32> = Lagoon::new();
+			lagoon.gen_pools();
+			lagoon.pools.io = Io::memory("").0;
+			lagoon.append_raw(source).unwrap();
+			let _ = lagoon.run_observed(&mut tracer);
+		}
+		let trace = String::from_utf8(tracer.finish().unwrap()).unwrap();
+		let execs: Vec<&str> = trace.lines().filter(|l| l.contains("cells")).collect();
+		assert_eq!(execs, vec![
+			"{\"kind\":\"exec-failed\",\"line\":1,\"column\":6,\"cells\":[{\"pool\":\"+\",\"index\":0,\"from\":0,\"to\":1}]}",
+			"{\"kind\":\"exec\",\"line\":1,\"column\":3,\"cells\":[{\"pool\":\"+\",\"index\":2,\"from\":0,\"to\":1}]}"
+		]);
+	}
+
+	#[test]
+	fn escapes() {
+		assert_eq!(string('"'), "\"\\\"\"");
+		assert_eq!(string('\\'), "\"\\\\\"");
+		assert_eq!(string('\n'), "\"\\u000a\"");
+		assert_eq!(string('p'), "\"p\"");
+	}
+}
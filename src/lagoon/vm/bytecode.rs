@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use crate::lagoon::index::Index;
+use crate::lagoon::interpreter::expression::{Closure, Expression};
+use crate::lagoon::span::Span;
+
+/// A single VM instruction. Pools are referred to by their position in the
+/// `Pools::order` the program was compiled against, and indices by their slot.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Op {
+	Toggle { pool: usize, slot: usize },
+	/// Toggles `slot` in a pool that did not exist at compile time, which is a
+	/// runtime error once it is reached
+	Missing { pool: char, slot: usize },
+	Exec,
+	/// Runs `Exec` that many times
+	Repeat(usize),
+	/// Starts a loop, jumping to `exit` unless every guard in
+	/// `guards[start..end]` holds
+	Loop { start: usize, end: usize, exit: usize },
+	/// Ends a loop by jumping back to its `Loop`
+	Back(usize)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Guard {
+	pub slot: usize,
+	pub negated: bool
+}
+
+/// A `Closure` flattened into `Op`s, where `spans[i]` is where `ops[i]` came
+/// from and `indices[slot]` is the index a slot stands for.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Program<T> {
+	pub ops: Vec<Op>,
+	pub spans: Vec<Span>,
+	pub guards: Vec<Guard>,
+	pub indices: Vec<T>,
+	/// The pool order the program was compiled against
+	pub pools: Vec<char>
+}
+
+/// Lowers `closure` to a `Program` for pools that run in `order`.
+pub fn compile<T: Index>(closure: &Closure<T>, order: &[char]) -> Program<T> {
+	let mut compiler = Compiler {
+		program: Program {
+			ops: Vec::new(),
+			spans: Vec::new(),
+			guards: Vec::new(),
+			indices: Vec::new(),
+			pools: order.to_vec()
+		},
+		slots: HashMap::new(),
+		ids: order.iter().enumerate().map(|(id, c)| (*c, id)).collect()
+	};
+	compiler.closure(closure);
+	compiler.program
+}
+
+struct Compiler<T> {
+	program: Program<T>,
+	slots: HashMap<T, usize>,
+	ids: HashMap<char, usize>
+}
+
+impl<T: Index> Compiler<T> {
+	fn slot(&mut self, index: T) -> usize {
+		let indices = &mut self.program.indices;
+		*self.slots.entry(index).or_insert_with(|| {
+			indices.push(index);
+			indices.len() - 1
+		})
+	}
+
+	fn emit(&mut self, op: Op, span: Span) -> usize {
+		self.program.ops.push(op);
+		self.program.spans.push(span);
+		self.program.ops.len() - 1
+	}
+
+	fn closure(&mut self, closure: &Closure<T>) {
+		for e in closure.parts.iter() {
+			match e {
+				Expression::Exec(span) => { self.emit(Op::Exec, *span); },
+				Expression::Repeat(n, span) => { self.emit(Op::Repeat(*n), *span); },
+				Expression::Instruction(i) => {
+					let slot = self.slot(i.index);
+					let op = match self.ids.get(&i.pool) {
+						Some(&pool) => Op::Toggle { pool, slot },
+						None => Op::Missing { pool: i.pool, slot }
+					};
+					self.emit(op, i.span);
+				},
+				Expression::Loop(l) => {
+					let start = self.program.guards.len();
+					for g in l.guard.iter() {
+						let slot = self.slot(g.index);
+						self.program.guards.push(Guard { slot, negated: g.negated });
+					}
+					let end = self.program.guards.len();
+					let head = self.emit(Op::Loop { start, end, exit: 0 }, l.span);
+					self.closure(&l.closure);
+					self.emit(Op::Back(head), l.span);
+					let exit = self.program.ops.len();
+					self.program.ops[head] = Op::Loop { start, end, exit };
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod lowering {
+	use crate::lagoon::interpreter::expression::factory as e;
+	use crate::lagoon::span::Span;
+	use super::{Guard, Op, compile};
+
+	#[test]
+	fn flat() {
+		let s = Span::default();
+		let closure = e::new_closure(vec![
+			e::new_instruction(4, '-', s),
+			e::new_instruction(2, '+', s),
+			e::new_instruction(4, 'x', s),
+			e::new_exec(s)
+		]);
+		let program = compile(&closure, &['+', '-']);
+		assert_eq!(program.ops, vec![
+			Op::Toggle { pool: 1, slot: 0 },
+			Op::Toggle { pool: 0, slot: 1 },
+			Op::Missing { pool: 'x', slot: 0 },
+			Op::Exec
+		]);
+		assert_eq!(program.indices, vec![4, 2]);
+	}
+
+	#[test]
+	fn nested_loops() {
+		// {0|1+ {!1|;} ;}
+		let s = Span::default();
+		let closure = e::new_closure(vec![
+			e::new_loop(vec![e::new_guard(0, false, s)], e::new_closure(vec![
+				e::new_instruction(1, '+', s),
+				e::new_loop(vec![e::new_guard(1, true, s)], e::new_closure(vec![
+					e::new_exec(s)
+				]), s),
+				e::new_exec(s)
+			]), s)
+		]);
+		let program = compile(&closure, &['+']);
+		assert_eq!(program.ops, vec![
+			Op::Loop { start: 0, end: 1, exit: 7 },
+			Op::Toggle { pool: 0, slot: 1 },
+			Op::Loop { start: 1, end: 2, exit: 5 },
+			Op::Exec,
+			Op::Back(2),
+			Op::Exec,
+			Op::Back(0)
+		]);
+		assert_eq!(program.guards, vec![
+			Guard { slot: 0, negated: false },
+			Guard { slot: 1, negated: true }
+		]);
+	}
+}
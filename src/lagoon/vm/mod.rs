@@ -0,0 +1,585 @@
+pub mod bytecode;
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+
+use super::cell::{Cell, Overflow};
+use super::executor::{Semantics, budget::{Budget, Limit, Meter}, error::RuntimeError};
+use super::index::Index;
+use super::io::Io;
+use super::pool::{self, Context, Edit, Memory, Operation};
+use super::span::Span;
+use self::bytecode::{Guard, Op, Program};
+
+pub use self::bytecode::compile;
+
+/// Runs compiled `Program`s against `memory` and `pools`, the same way an
+/// `Executor` runs the closure they came from. Memory is kept in dense slots
+/// and pools are looked up by id for the length of the run, then everything
+/// is written back, even if the run fails.
+pub struct Vm<'a, T: Index, C = i32> {
+	pub memory: &'a mut HashMap<T, C>,
+	pub pools: &'a mut pool::Pools<T, C>,
+	pub semantics: Semantics,
+	pub overflow: Overflow,
+	pub meter: Meter,
+	/// Whether `{..|;}` loops over pools that only add may be run in one go
+	pub accelerate: bool
+}
+
+impl<'a, T: Index, C: Cell> Vm<'a, T, C> {
+	pub fn new(memory: &'a mut HashMap<T, C>, pools: &'a mut pool::Pools<T, C>) -> Self {
+		Self {
+			memory,
+			pools,
+			semantics: Semantics::Sequential,
+			overflow: Overflow::default(),
+			meter: Meter::new(Budget::default()),
+			accelerate: true
+		}
+	}
+
+	pub fn with_semantics(mut self, semantics: Semantics) -> Self {
+		self.semantics = semantics;
+		return self;
+	}
+
+	pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+		self.overflow = overflow;
+		return self;
+	}
+
+	/// Starts the clock on `budget`.
+	pub fn with_budget(mut self, budget: Budget) -> Self {
+		self.meter = Meter::new(budget);
+		return self;
+	}
+
+	/// Limits just the number of `;`.
+	pub fn with_step_limit(self, steps: Option<usize>) -> Self {
+		let budget = Budget { steps, ..self.meter.budget };
+		self.with_budget(budget)
+	}
+
+	pub fn with_acceleration(mut self, accelerate: bool) -> Self {
+		self.accelerate = accelerate;
+		return self;
+	}
+
+	pub fn run(&mut self, program: &Program<T>) -> Result<(), RuntimeError<T>> {
+		let mut slots = Slots::new(program, self.memory);
+		let mut members: Vec<BTreeMap<T, usize>> = Vec::new();
+		for c in program.pools.iter() {
+			let set = self.pools.map.get(c).map(|p| &p.set).into_iter().flatten();
+			members.push(set.map(|i| (*i, slots.slot(*i))).collect());
+		}
+
+		let mut funcs: Vec<Option<&mut Box<dyn Operation<T, C>>>> =
+			program.pools.iter().map(|_| None).collect();
+		for (c, p) in self.pools.map.iter_mut() {
+			if let Some(id) = program.pools.iter().position(|d| d == c) {
+				funcs[id] = Some(&mut p.func);
+			}
+		}
+		let deltas = funcs.iter().map(|f| f.as_ref().and_then(|f| f.delta())).collect();
+		let mut machine = Machine {
+			program,
+			slots,
+			members,
+			funcs,
+			deltas,
+			io: &mut self.pools.io,
+			semantics: self.semantics,
+			overflow: self.overflow,
+			meter: self.meter.clone(),
+			accelerate: self.accelerate
+		};
+		let result = machine.run();
+		self.meter = machine.meter;
+		slots = machine.slots;
+		members = machine.members;
+
+		for (index, value) in slots.indices.into_iter().zip(slots.cells) {
+			if let Some(value) = value { self.memory.insert(index, value); }
+		}
+		for (c, set) in program.pools.iter().zip(members) {
+			if let Some(p) = self.pools.map.get_mut(c) {
+				p.set = set.into_keys().collect();
+			}
+		}
+		result
+	}
+}
+
+/// Memory as dense slots, where `cells[slot]` is `None` until the index it
+/// stands for gets a value.
+struct Slots<T, C> {
+	cells: Vec<Option<C>>,
+	indices: Vec<T>,
+	slot_of: HashMap<T, usize>
+}
+
+impl<T: Index, C: Cell> Slots<T, C> {
+	fn new(program: &Program<T>, memory: &HashMap<T, C>) -> Self {
+		let mut slots = Self {
+			cells: program.indices.iter().map(|i| memory.get(i).cloned()).collect(),
+			indices: program.indices.clone(),
+			slot_of: program.indices.iter().enumerate().map(|(s, i)| (*i, s)).collect()
+		};
+		for (index, value) in memory.iter() {
+			let slot = slots.slot(*index);
+			slots.cells[slot] = Some(value.clone());
+		}
+		slots
+	}
+
+	/// The slot for `index`, making one if it has none yet.
+	fn slot(&mut self, index: T) -> usize {
+		if let Some(slot) = self.slot_of.get(&index) { return *slot; }
+		self.cells.push(None);
+		self.indices.push(index);
+		self.slot_of.insert(index, self.indices.len() - 1);
+		self.indices.len() - 1
+	}
+
+	fn holds(&self, guard: &Guard) -> bool {
+		let value = self.cells[guard.slot].as_ref().map_or(false, |v| !v.is_zero());
+		value != guard.negated
+	}
+}
+
+impl<T: Index, C> Memory<T, C> for Slots<T, C> {
+	fn get(&self, index: &T) -> Option<&C> {
+		self.slot_of.get(index).and_then(|slot| self.cells[*slot].as_ref())
+	}
+}
+
+/// The state of a single run, with every pool resolved to its id.
+struct Machine<'a, 'p, T: Index, C> {
+	program: &'p Program<T>,
+	slots: Slots<T, C>,
+	/// The indices in each pool, along with their slots
+	members: Vec<BTreeMap<T, usize>>,
+	/// Each pool's operation, or `None` for pools that went missing
+	funcs: Vec<Option<&'a mut Box<dyn Operation<T, C>>>>,
+	/// Each pool's `Operation::delta`
+	deltas: Vec<Option<i64>>,
+	io: &'a mut Io,
+	semantics: Semantics,
+	overflow: Overflow,
+	meter: Meter,
+	accelerate: bool
+}
+
+impl<T: Index, C: Cell> Machine<'_, '_, T, C> {
+	fn run(&mut self) -> Result<(), RuntimeError<T>> {
+		let program = self.program;
+		let mut pc = 0;
+		while let Some(op) = program.ops.get(pc) {
+			match *op {
+				Op::Toggle { pool, slot } => {
+					self.meter.instruction().map_err(|limit| self.out_of_budget(limit, program.spans[pc]))?;
+					let members = &mut self.members[pool];
+					let index = program.indices[slot];
+					if members.remove(&index).is_none() { members.insert(index, slot); }
+				},
+				Op::Missing { pool, slot } => {
+					self.meter.instruction().map_err(|limit| self.out_of_budget(limit, program.spans[pc]))?;
+					return Err(RuntimeError::UnknownPool {
+						pool,
+						index: program.indices[slot],
+						span: program.spans[pc]
+					});
+				},
+				Op::Exec => self.exec(program.spans[pc])?,
+				Op::Repeat(n) => for _ in 0..n { self.exec(program.spans[pc])? },
+				Op::Loop { start, end, exit } => {
+					let guards = &program.guards[start..end];
+					if !guards.iter().all(|g| self.slots.holds(g)) {
+						pc = exit;
+						continue;
+					}
+					// A body of just `;` runs the same step until a guard fails
+					if self.accelerate && exit == pc + 3 && program.ops[pc + 1] == Op::Exec {
+						if !self.skip(guards) {
+							while guards.iter().all(|g| self.slots.holds(g)) {
+								self.meter.iteration().map_err(|limit| self.out_of_budget(limit, program.spans[pc]))?;
+								self.exec(program.spans[pc + 1])?;
+							}
+						}
+						pc = exit;
+						continue;
+					}
+					self.meter.iteration().map_err(|limit| self.out_of_budget(limit, program.spans[pc]))?;
+				},
+				Op::Back(head) => {
+					pc = head;
+					continue;
+				}
+			}
+			pc += 1;
+		}
+		Ok(())
+	}
+
+	fn exec(&mut self, span: Span) -> Result<(), RuntimeError<T>> {
+		self.meter.step().map_err(|limit| self.out_of_budget(limit, span))?;
+		let mut edits: Vec<(char, T, Edit)> = Vec::new();
+		match self.semantics {
+			Semantics::Sequential => self.exec_sequential(span, &mut edits)?,
+			Semantics::Simultaneous => self.exec_simultaneous(span, &mut edits)?
+		}
+		for (id, index, edit) in edits {
+			self.edit(edit).map_err(|target| RuntimeError::UnknownTarget {
+				pool: id,
+				target,
+				index,
+				span
+			})?;
+		}
+		Ok(())
+	}
+
+	fn exec_sequential(
+		&mut self,
+		span: Span,
+		edits: &mut Vec<(char, T, Edit)>
+	) -> Result<(), RuntimeError<T>> {
+		let mut queued: Vec<Edit> = Vec::new();
+		for (id, func) in self.funcs.iter_mut().enumerate() {
+			let func = match func { Some(func) => func, None => continue };
+			let c = self.program.pools[id];
+			for (index, slot) in self.members[id].iter() {
+				let mut datum = self.slots.cells[*slot].clone().unwrap_or_default();
+				let mut context = Context {
+					index,
+					memory: &self.slots,
+					io: self.io,
+					edits: &mut queued,
+					overflow: self.overflow
+				};
+				let result = func.apply(&mut datum, &mut context);
+				self.slots.cells[*slot] = Some(datum);
+				result.map_err(|f| RuntimeError::from_fault(f, c, *index, span))?;
+				edits.extend(queued.drain(..).map(|edit| (c, *index, edit)));
+			}
+		}
+		Ok(())
+	}
+
+	fn exec_simultaneous(
+		&mut self,
+		span: Span,
+		edits: &mut Vec<(char, T, Edit)>
+	) -> Result<(), RuntimeError<T>> {
+		let o = self.overflow;
+		let mut queued: Vec<Edit> = Vec::new();
+		// Only the total has to fit in a cell, as in the executor
+		let mut deltas: BTreeMap<T, (C::Delta, char, usize)> = BTreeMap::new();
+		for (id, func) in self.funcs.iter_mut().enumerate() {
+			let func = match func { Some(func) => func, None => continue };
+			let c = self.program.pools[id];
+			for (index, slot) in self.members[id].iter() {
+				let before = self.slots.cells[*slot].clone().unwrap_or_default();
+				let mut after = before.clone();
+				let mut context = Context {
+					index,
+					memory: &self.slots,
+					io: self.io,
+					edits: &mut queued,
+					overflow: o
+				};
+				func.apply(&mut after, &mut context)
+					.map_err(|f| RuntimeError::from_fault(f, c, *index, span))?;
+				edits.extend(queued.drain(..).map(|edit| (c, *index, edit)));
+				let total = deltas.entry(*index).or_insert((C::Delta::default(), c, *slot));
+				total.0 += before.change(&after);
+				total.1 = c;
+			}
+		}
+		for (index, (delta, c, slot)) in deltas {
+			let datum = self.slots.cells[slot].get_or_insert_with(C::default);
+			*datum = datum.shift(&delta, o).ok_or(RuntimeError::Overflow { pool: c, index, span })?;
+		}
+		Ok(())
+	}
+
+	/// Runs a `{..|;}` loop whose guards hold in one go, if every pool with
+	/// indices in it only adds to them and the loop is sure to end without
+	/// overflowing or running out of steps. Returns whether it did.
+	fn skip(&mut self, guards: &[Guard]) -> bool {
+		// The total each slot moves by per step, and the most it moves up and
+		// down within one
+		let mut moves: BTreeMap<usize, (i64, i64, i64)> = BTreeMap::new();
+		for (members, delta) in self.members.iter().zip(self.deltas.iter()) {
+			if members.is_empty() { continue; }
+			let delta = match delta { Some(delta) => *delta, None => return false };
+			for slot in members.values() {
+				let total = moves.entry(*slot).or_insert((0, 0, 0));
+				total.0 += delta;
+				if delta > 0 { total.1 += delta; } else { total.2 += delta; }
+			}
+		}
+
+		let mut steps: Option<i64> = None;
+		for g in guards {
+			let delta = moves.get(&g.slot).map_or(0, |m| m.0);
+			if delta == 0 { continue; }
+			if g.negated { return false; }
+			let value = match self.slots.cells[g.slot].as_ref().and_then(|v| v.to_i64()) {
+				Some(value) => value,
+				None => return false
+			};
+			// Only a guard that lands on zero exactly stops the loop
+			if value % delta != 0 || (value > 0) == (delta > 0) { return false; }
+			let n = value / -delta;
+			steps = Some(steps.map_or(n, |s| s.min(n)));
+		}
+		let steps = match steps { Some(steps) => steps, None => return false };
+		if !self.meter.allows(steps as usize) { return false; }
+
+		let fits = |n: i128| i64::try_from(n).ok().and_then(|n| C::from_i64(n, Overflow::Check)).is_some();
+		let mut results: Vec<(usize, C)> = Vec::new();
+		for (slot, (total, up, down)) in moves {
+			let start = match self.slots.cells[slot].clone().unwrap_or_default().to_i64() {
+				Some(start) => start as i128,
+				None => return false
+			};
+			let end = start + steps as i128 * total as i128;
+			if self.overflow == Overflow::Check
+				&& !(fits(start.min(end) + down as i128) && fits(start.max(end) + up as i128)) {
+				return false;
+			}
+			match i64::try_from(end).ok().and_then(|end| C::from_i64(end, self.overflow)) {
+				Some(value) => results.push((slot, value)),
+				None => return false
+			}
+		}
+		for (slot, value) in results { self.slots.cells[slot] = Some(value); }
+		self.meter.steps += steps as usize;
+		self.meter.iterations += steps as usize;
+		true
+	}
+
+	fn out_of_budget(&self, limit: Limit, span: Span) -> RuntimeError<T> {
+		let cells = self.slots.indices.iter().zip(self.slots.cells.iter());
+		RuntimeError::out_of_budget(limit, span, cells.filter_map(|(i, v)| v.as_ref().map(|v| (i, v))))
+	}
+
+	/// Like `Pools::edit`, on the pools' members as the VM holds them.
+	fn edit(&mut self, edit: Edit) -> Result<(), char> {
+		let pools = &self.program.pools;
+		let id = |c: char| pools.iter().position(|d| *d == c).ok_or(c);
+		match edit {
+			Edit::Shift { pool, by } => {
+				let id = id(pool)?;
+				let shifted: Vec<T> = self.members[id].keys().filter_map(|i| i.offset(by)).collect();
+				self.members[id] = shifted.into_iter().map(|i| (i, self.slots.slot(i))).collect();
+			},
+			Edit::Rotate(a, b) => {
+				let (a, b) = (id(a)?, id(b)?);
+				self.members.swap(a, b);
+			},
+			Edit::Clear(pool) => self.members[id(pool)?].clear()
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod equivalence {
+	use std::collections::HashMap;
+
+	use crate::lagoon::Lagoon;
+	use crate::lagoon::cell::Overflow;
+	use crate::lagoon::executor::{Executor, Semantics, error::RuntimeError};
+	use crate::lagoon::io::Io;
+	use crate::lagoon::pool::Edit;
+	use super::{Vm, compile};
+
+	type Outcome = (Result<(), RuntimeError<i32>>, HashMap<i32, i32>, Vec<(char, Vec<i32>)>, String);
+
+	fn lagoon() -> Lagoon<i32> {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.pools.add_edit('>', Edit::Shift { pool: '+', by: 1 });
+		lagoon.pools.add_edit('r', Edit::Rotate('+', '-'));
+		lagoon.pools.add_edit('s', Edit::Rotate('+', '+'));
+		lagoon
+	}
+
+	/// Runs `source` on the executor and on the VM, returning what each left
+	/// behind.
+	fn both(source: &str, input: &str, semantics: Semantics, step_limit: Option<usize>) -> (Outcome, Outcome) {
+		let run = |vm: bool| {
+			let mut lagoon = lagoon();
+			let (io, output) = Io::memory(input);
+			lagoon.pools.io = io;
+			lagoon.append_raw(source).unwrap();
+			let code = lagoon.code.clone();
+			let result = if vm {
+				let program = compile(&code, &lagoon.pools.order);
+				Vm::new(&mut lagoon.memory, &mut lagoon.pools)
+					.with_semantics(semantics)
+					.with_overflow(Overflow::Check)
+					.with_step_limit(step_limit)
+					.run(&program)
+			} else {
+				Executor::new(&mut lagoon.memory, &mut lagoon.pools)
+					.with_semantics(semantics)
+					.with_step_limit(step_limit)
+					.run(&code)
+			};
+			let pools = lagoon.pools.order.iter()
+				.map(|c| (*c, lagoon.pools.map[c].set.iter().copied().collect()))
+				.collect();
+			(result, lagoon.memory, pools, output.contents())
+		};
+		(run(false), run(true))
+	}
+
+	const MULTIPLY: &str = "
+		0i 1i; 0i 1i
+		{0 | 0-;0- 2+ 1- {1 | ;} 2+ 1- 2- 1+ 3+ {2 | ;} 2- 1+ 3+}
+		0o 1o 2o 3o;";
+
+	#[test]
+	fn programs() {
+		let programs = [
+			(MULTIPLY, "3 4"),
+			("0+;;; 0+ 0- 1+ {0|;} 1= 2= 2* 0o 1o 2o;", ""),
+			("0+ 0>;;; 0+ 0r; 1o 2o 3o;", ""),
+			("0+ 1+ 0s; 0o 1o;", ""),
+			("{!0 | 0+; 0+} 5+ {!5 | 5+; 5* 5*;;; 5*} 5o;", ""),
+			("0i; 0o {0 | 0-; 0- 0i; 0o}", "2 1 0")
+		];
+		for semantics in [Semantics::Sequential, Semantics::Simultaneous] {
+			for (source, input) in programs.iter() {
+				let (executor, vm) = both(source, input, semantics, None);
+				assert!(executor.0.is_ok(), "{}: {:?}", source, executor.0);
+				assert_eq!(executor, vm, "{}", source);
+			}
+		}
+	}
+
+	/// A pool may move a cell further than it can hold in one go, as long as
+	/// where it ends up fits.
+	#[test]
+	fn large_moves() {
+		for semantics in [Semantics::Sequential, Semantics::Simultaneous] {
+			let (executor, vm) = both("0i; 0i 0i;", "2000000000 -2000000000", semantics, None);
+			assert_eq!(executor.0, Ok(()));
+			assert_eq!(executor.1[&0], -2_000_000_000);
+			assert_eq!(executor, vm);
+		}
+	}
+
+	#[test]
+	fn errors() {
+		let programs = [
+			("0+;\n;\n 0i;", "", None),
+			("0+; {0 | ;}", "", Some(100)),
+			("1+ 1* 1o; {1 | ;}", "", None)
+		];
+		for semantics in [Semantics::Sequential, Semantics::Simultaneous] {
+			for (source, input, limit) in programs.iter() {
+				let (executor, vm) = both(source, input, semantics, *limit);
+				assert!(executor.0.is_err(), "{}", source);
+				assert_eq!(executor, vm, "{}", source);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod acceleration {
+	use crate::lagoon::Lagoon;
+	use crate::lagoon::cell::{Cell, Overflow};
+	use crate::lagoon::executor::{Semantics, error::RuntimeError};
+	use crate::lagoon::io::Io;
+	use super::{Vm, compile};
+
+	type Outcome<C> = (Result<(), RuntimeError<i32>>, Vec<(i32, C)>, String, usize);
+
+	/// Runs `source` on a VM after setting up `memory`, returning the result,
+	/// the memory, the output and how many steps it took.
+	fn run<C: Cell>(
+		source: &str,
+		memory: &[(i32, C)],
+		semantics: Semantics,
+		overflow: Overflow,
+		step_limit: Option<usize>,
+		accelerate: bool
+	) -> Outcome<C> {
+		let mut lagoon: Lagoon<i32, C> = Lagoon::new();
+		lagoon.gen_pools();
+		lagoon.pools.add_pool('d', |datum, context| {
+			*datum = context.add(datum, -1)?;
+			Ok(())
+		});
+		let (io, output) = Io::memory("");
+		lagoon.pools.io = io;
+		lagoon.memory.extend(memory.iter().cloned());
+		lagoon.append_raw(source).unwrap();
+		let program = compile(&lagoon.code, &lagoon.pools.order);
+		let mut vm = Vm::new(&mut lagoon.memory, &mut lagoon.pools)
+			.with_semantics(semantics)
+			.with_overflow(overflow)
+			.with_step_limit(step_limit)
+			.with_acceleration(accelerate);
+		let result = vm.run(&program);
+		let steps = vm.meter.steps;
+		let mut memory: Vec<_> = lagoon.memory.into_iter().collect();
+		memory.sort_by_key(|(i, _)| *i);
+		(result, memory, output.contents(), steps)
+	}
+
+	fn same<C: Cell>(source: &str, memory: &[(i32, C)], overflow: Overflow, step_limit: Option<usize>) {
+		for semantics in [Semantics::Sequential, Semantics::Simultaneous] {
+			let run = |accelerate| run(source, memory, semantics, overflow, step_limit, accelerate);
+			assert_eq!(run(true), run(false), "{}", source);
+		}
+	}
+
+	#[test]
+	fn transfers() {
+		let programs = [
+			"0+;;;;;; 0+ 0- 1+ {0|;} 0- 1+ 1o;",
+			"0+;;;;;; 0+ 0- 0- 1+ 2+ 2+ {0|;} 1o 2o;",
+			"0+;;;;;; 0+ 0- 1- {0, 2|;} 2- 1o;",
+			"0+;;;;;; 0+ 0- 1- {0, !2|;} 0- 1o;",
+			"0+;;;;; 0+ 0- 0- {0|;}",
+			"0+;;;;;; 0+ 0- 0o {0|;} 0o",
+			"0+;;;;;; 0+ 0d 1+ {0|;} 0d 1o;"
+		];
+		for source in programs.iter() {
+			same::<i32>(source, &[], Overflow::Check, Some(1000));
+		}
+	}
+
+	#[test]
+	fn limits() {
+		same::<i32>("0+ 1+;;;;; 0+ 0- {1|;}", &[], Overflow::Check, Some(7));
+		same::<i32>("0- 1+ {0|;}", &[(0, 5), (1, i32::MAX - 4)], Overflow::Check, None);
+		same::<i32>("0- 1+ {0|;}", &[(0, 5), (1, i32::MAX - 3)], Overflow::Check, None);
+		same::<i32>("0- 1+ 1- 1+ {0|;}", &[(0, 5), (1, i32::MAX - 5)], Overflow::Check, None);
+		same::<u8>("0- 1- {0|;}", &[(0, 5), (1, 3)], Overflow::Check, None);
+		same::<u8>("0- 1- {0|;}", &[(0, 5), (1, 3)], Overflow::Wrap, None);
+		same::<u8>("0+ 1- {0|;}", &[(0, 5), (1, 3)], Overflow::Wrap, None);
+	}
+
+	#[test]
+	fn skips_ahead() {
+		let (result, memory, _, steps) = run::<i64>(
+			"0- 1+ 2- {0|;}",
+			&[(0, 3_000_000_000)],
+			Semantics::Sequential,
+			Overflow::Check,
+			None,
+			true
+		);
+		assert_eq!(result, Ok(()));
+		assert_eq!(steps, 3_000_000_000);
+		assert_eq!(memory, vec![(0, 0), (1, 3_000_000_000), (2, -3_000_000_000)]);
+	}
+}
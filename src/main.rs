@@ -1,17 +1,260 @@
+#![allow(clippy::needless_return)]
+
 mod lagoon;
+mod repl;
+mod golden;
+
+use std::{env, fs, io::{self, BufWriter, Read}, process, time::Duration};
+
+use lagoon::{Lagoon, LagoonConfig, optimize::Passes};
+use lagoon::executor::{Semantics, budget::Budget, error::RuntimeError};
+use lagoon::cell::{Cell, CellType, Overflow};
+use lagoon::io::{Input, InputMode, Io, Output, OutputMode};
+use lagoon::{coverage::Coverage, profile::Profiler, trace::Tracer};
+
+const USAGE: &str = "\
+usage: lagoon [options] <file.lag | ->
+       lagoon repl [options]
+       lagoon test [options] [--bless] <file.lag | dir>...
+options: --chars --simultaneous --cell <u8|i32|i64|bigint> --wrap --optimize
+         --trace <file> --profile <file> --folded <file> --coverage <file> --lcov <file>
+         --max-steps <n> --max-instructions <n> --max-iterations <n> --timeout <secs>
+--trace, --profile, --folded, --coverage and --lcov run the program without --optimize";
+
+/// What `lagoon` was asked to do.
+enum Command {
+	/// Run the program at the path
+	Run(String),
+	Repl,
+	/// Run the programs at or under the paths as golden tests
+	Test(Vec<String>)
+}
 
-// use std::io::{self, BufRead};
-// use lagoon::Lagoon;
+struct Options {
+	command: Command,
+	/// Whether `lagoon test` should rewrite expectations instead of checking them
+	bless: bool,
+	chars: bool,
+	semantics: Semantics,
+	budget: Budget,
+	/// Which `Cell` type to build the `Lagoon` with
+	cell: CellType,
+	config: LagoonConfig,
+	passes: Passes,
+	/// Where to write a trace of the run to
+	trace: Option<String>,
+	/// Where to write the annotated listing of a profile to
+	profile: Option<String>,
+	/// Where to write a profile as folded stacks to
+	folded: Option<String>,
+	/// Where to write the annotated listing of the coverage to
+	coverage: Option<String>,
+	/// Where to write the coverage as an lcov tracefile to
+	lcov: Option<String>
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+	let mut command: Option<Command> = None;
+	let mut bless = false;
+	let mut chars = false;
+	let mut semantics = Semantics::Sequential;
+	let mut budget = Budget::default();
+	let mut cell = CellType::default();
+	let mut config = LagoonConfig::default();
+	let mut passes = Passes::default();
+	let mut trace = None;
+	let mut profile = None;
+	let mut folded = None;
+	let mut coverage = None;
+	let mut lcov = None;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--chars" => chars = true,
+			"--simultaneous" => semantics = Semantics::Simultaneous,
+			"--max-steps" => budget.steps = Some(limit(&arg, args.next())?),
+			"--max-instructions" => budget.instructions = Some(limit(&arg, args.next())?),
+			"--max-iterations" => budget.iterations = Some(limit(&arg, args.next())?),
+			"--timeout" => {
+				let secs = args.next().ok_or("--timeout needs a number of seconds")?;
+				let time = secs.parse().ok().and_then(|s| Duration::try_from_secs_f64(s).ok());
+				budget.time = Some(time.ok_or_else(|| format!("invalid timeout `{}`", secs))?);
+			},
+			"--cell" => cell = args.next().ok_or("--cell needs a type")?.parse()?,
+			"--wrap" => config.overflow = Overflow::Wrap,
+			"--optimize" => passes = Passes::all(),
+			"--trace" => trace = Some(args.next().ok_or("--trace needs a file")?),
+			"--profile" => profile = Some(args.next().ok_or("--profile needs a file")?),
+			"--folded" => folded = Some(args.next().ok_or("--folded needs a file")?),
+			"--coverage" => coverage = Some(args.next().ok_or("--coverage needs a file")?),
+			"--lcov" => lcov = Some(args.next().ok_or("--lcov needs a file")?),
+			"--bless" => bless = true,
+			"repl" if command.is_none() => command = Some(Command::Repl),
+			"test" if command.is_none() => command = Some(Command::Test(Vec::new())),
+			_ => match &mut command {
+				None => command = Some(Command::Run(arg)),
+				Some(Command::Test(paths)) => paths.push(arg),
+				Some(_) => return Err(format!("unexpected argument `{}`", arg))
+			}
+		}
+	}
+	let command = match command {
+		Some(Command::Test(paths)) if paths.is_empty() => return Err("no tests given".to_string()),
+		Some(command) => command,
+		None => return Err("no program given".to_string())
+	};
+	if bless && !matches!(command, Command::Test(_)) {
+		return Err("--bless only goes with `lagoon test`".to_string());
+	}
+	Ok(Options { command, bless, chars, semantics, budget, cell, config, passes, trace, profile, folded, coverage, lcov })
+}
+
+/// The number following a `--max-*` option.
+fn limit(option: &str, n: Option<String>) -> Result<usize, String> {
+	let n = n.ok_or_else(|| format!("{} needs a number", option))?;
+	n.parse().map_err(|_| format!("invalid limit `{}`", n))
+}
 
 fn main() {
-	// #[allow(unused_variables)]
-	// let mut lagoon = Lagoon::new();
-	// lagoon.gen_pools();
-	// Parser::parse("this is a test	asdf".to_string());
-
-	// let stdin = io::stdin();
-	// for line in stdin.lock().lines() {
-	// 	let line = line.expect("Could not read line from standard in");
-	// 	println!("{}", line);
-	// }
+	let options = match parse_args(env::args().skip(1)) {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("lagoon: {}\n{}", e, USAGE);
+			process::exit(2);
+		}
+	};
+	match options.cell {
+		CellType::U8 => start::<u8>(options),
+		CellType::I32 => start::<i32>(options),
+		CellType::I64 => start::<i64>(options),
+		#[cfg(feature = "bigint")]
+		CellType::BigInt => start::<num_bigint::BigInt>(options)
+	}
+}
+
+/// A `Lagoon` set up the way `options` says, reading and writing nothing.
+fn lagoon<C: Cell>(options: &Options) -> Lagoon<i32, C> {
+	let mut lagoon: Lagoon<i32, C> = Lagoon::with_config(options.config);
+	lagoon.gen_pools();
+	lagoon.budget = options.budget;
+	lagoon.semantics = options.semantics;
+	lagoon.passes = options.passes;
+	lagoon
+}
+
+fn start<C: Cell>(options: Options) {
+	if let Command::Test(paths) = &options.command {
+		let mut budget = options.budget;
+		budget.steps = budget.steps.or(Some(golden::DEFAULT_STEPS));
+		let new = || Lagoon { budget, ..lagoon::<C>(&options) };
+		match golden::run(paths, new, options.chars, options.bless, &mut io::stdout()) {
+			Ok(true) => return,
+			Ok(false) => process::exit(1),
+			Err(e) => {
+				eprintln!("lagoon: {}", e);
+				process::exit(1);
+			}
+		}
+	}
+	let mut lagoon: Lagoon<i32, C> = lagoon(&options);
+	lagoon.pools.io = if options.chars {
+		Io::new(
+			Input::stdin().with_mode(InputMode::Character),
+			Output::stdout().with_mode(OutputMode::Character)
+		)
+	} else {
+		Io::stdio()
+	};
+
+	let path = match &options.command {
+		Command::Run(path) => path,
+		_ => {
+			let mut input = Input::stdin();
+			if let Err(e) = repl::run(&mut lagoon, &mut input, &mut io::stdout()) {
+				eprintln!("lagoon: {}", e);
+				process::exit(1);
+			}
+			return;
+		}
+	};
+	let source = match read_source(path) {
+		Ok(source) => source,
+		Err(e) => {
+			eprintln!("lagoon: could not read {}: {}", path, e);
+			process::exit(1);
+		}
+	};
+	if let Err(e) = lagoon.append(source.clone()) {
+		for d in e.diagnostics {
+			eprintln!("{}:{}", path, d);
+		}
+		process::exit(1);
+	}
+	let observed = [&options.trace, &options.profile, &options.folded, &options.coverage, &options.lcov]
+		.iter().any(|file| file.is_some());
+	let result = if observed { run_observed(&mut lagoon, &options, path, &source) } else { lagoon.run() };
+	lagoon.pools.io.output.finish().ok();
+	if let Err(e) = result {
+		eprintln!("{}:{}", path, e);
+		process::exit(1);
+	}
+}
+
+/// Runs `lagoon` with a trace, profile or coverage of it written wherever
+/// `options` says.
+fn run_observed<C: Cell>(
+	lagoon: &mut Lagoon<i32, C>,
+	options: &Options,
+	path: &str,
+	source: &str
+) -> Result<(), RuntimeError<i32>> {
+	let mut tracer = options.trace.as_ref().map(|trace| Tracer::new(BufWriter::new(create(trace))));
+	let mut profiler = if options.profile.is_some() || options.folded.is_some() { Some(Profiler::new()) } else { None };
+	let mut coverage = if options.coverage.is_some() || options.lcov.is_some() {
+		Some(Coverage::new(&lagoon.code))
+	} else {
+		None
+	};
+	let result = lagoon.run_observed(((&mut tracer, &mut profiler), &mut coverage));
+	if let (Some(tracer), Some(trace)) = (tracer, &options.trace) {
+		if let Err(e) = tracer.finish() { fail_writing(trace, e); }
+	}
+	if let Some(profiler) = profiler {
+		if let Some(profile) = &options.profile {
+			fs::write(profile, profiler.listing(source)).unwrap_or_else(|e| fail_writing(profile, e));
+		}
+		if let Some(folded) = &options.folded {
+			fs::write(folded, profiler.folded(path)).unwrap_or_else(|e| fail_writing(folded, e));
+		}
+	}
+	if let Some(coverage) = coverage {
+		if let Some(listing) = &options.coverage {
+			fs::write(listing, coverage.listing(source)).unwrap_or_else(|e| fail_writing(listing, e));
+		}
+		if let Some(lcov) = &options.lcov {
+			fs::write(lcov, coverage.lcov(path)).unwrap_or_else(|e| fail_writing(lcov, e));
+		}
+	}
+	result
+}
+
+fn create(path: &str) -> fs::File {
+	fs::File::create(path).unwrap_or_else(|e| {
+		eprintln!("lagoon: could not create {}: {}", path, e);
+		process::exit(1);
+	})
+}
+
+fn fail_writing(path: &str, e: io::Error) -> ! {
+	eprintln!("lagoon: could not write {}: {}", path, e);
+	process::exit(1);
+}
+
+/// Reads the program from `path`, or from standard in when `path` is `-`.
+fn read_source(path: &str) -> io::Result<String> {
+	if path == "-" {
+		let mut source = String::new();
+		io::stdin().read_to_string(&mut source)?;
+		return Ok(source);
+	}
+	fs::read_to_string(path)
 }
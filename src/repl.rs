@@ -0,0 +1,150 @@
+use std::{fs, mem};
+use std::io::{self, Write};
+
+use crate::lagoon::{Lagoon, cell::Cell, error::Error, io::Input, parser};
+
+const HELP: &str = "\
+:mem          show every cell that has been touched
+:pools        show the indices in each pool that has any
+:reset        clear memory, pools and code
+:load <file>  run a file against the current state
+:help         show this message
+:quit         leave the repl";
+
+/// Reads lines from `input` and runs each one against `lagoon` as it comes in,
+/// so memory and pools carry over from one line to the next. A line that
+/// leaves a `{` or a block comment open is held until the rest of it arrives.
+/// Prompts, meta-command output and errors go to `out`; whatever the program
+/// writes still goes through the pools' `io`.
+pub fn run<C: Cell>(lagoon: &mut Lagoon<i32, C>, input: &mut Input, out: &mut impl Write) -> io::Result<()> {
+	let mut pending = String::new();
+	loop {
+		write!(out, "{}", if pending.is_empty() { "> " } else { ". " })?;
+		out.flush()?;
+		let line = match input.read_line()? {
+			Some(line) => line,
+			None => break
+		};
+		if pending.is_empty() {
+			if let Some(command) = line.trim().strip_prefix(':') {
+				if !meta(lagoon, command, out)? { return Ok(()); }
+				continue;
+			}
+		}
+		pending.push_str(&line);
+		if !parser::is_incomplete(&pending) {
+			eval(lagoon, mem::take(&mut pending), None, out)?;
+		}
+	}
+	writeln!(out)?;
+	// Whatever was left open still gets reported instead of vanishing.
+	if !pending.trim().is_empty() { eval(lagoon, pending, None, out)?; }
+	Ok(())
+}
+
+/// Runs a meta-command, returning `false` when the repl should stop.
+fn meta<C: Cell>(lagoon: &mut Lagoon<i32, C>, command: &str, out: &mut impl Write) -> io::Result<bool> {
+	let mut words = command.split_whitespace();
+	match words.next().unwrap_or("") {
+		"mem" => {
+			let mut cells: Vec<_> = lagoon.memory.iter().collect();
+			cells.sort_by_key(|(index, _)| **index);
+			for (index, value) in cells { writeln!(out, "{}: {}", index, value)?; }
+		},
+		"pools" => {
+			for c in &lagoon.pools.order {
+				if lagoon.pools.map[c].set.is_empty() { continue; }
+				let indices: Vec<String> = lagoon.pools.map[c].set.iter()
+					.map(|i| i.to_string())
+					.collect();
+				writeln!(out, "{}: {}", c, indices.join(" "))?;
+			}
+		},
+		"reset" => lagoon.reset(),
+		"load" => match words.next() {
+			Some(path) => match fs::read_to_string(path) {
+				Ok(source) => eval(lagoon, source, Some(path), out)?,
+				Err(e) => writeln!(out, "could not read {}: {}", path, e)?
+			},
+			None => writeln!(out, ":load needs a file")?
+		},
+		"help" => writeln!(out, "{}", HELP)?,
+		"quit" | "q" => return Ok(false),
+		other => writeln!(out, "unknown command `:{}`, try :help", other)?
+	}
+	Ok(true)
+}
+
+/// Evaluates `source` and reports any error, prefixed with `path` when it came
+/// from a file.
+fn eval<C: Cell>(lagoon: &mut Lagoon<i32, C>, source: String, path: Option<&str>, out: &mut impl Write) -> io::Result<()> {
+	let prefix = path.map(|p| format!("{}:", p)).unwrap_or_default();
+	let result = lagoon.eval(source);
+	lagoon.pools.io.output.finish()?;
+	match result {
+		Ok(()) => {},
+		Err(Error::Parse(e)) => {
+			for d in e.diagnostics { writeln!(out, "{}{}", prefix, d)?; }
+		},
+		Err(Error::Runtime(e)) => writeln!(out, "{}{}", prefix, e)?
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod session {
+	use std::io::Cursor;
+	use crate::lagoon::{Lagoon, io::{Buffer, Input, Io}};
+
+	/// Runs `lines` through the repl, returning what it printed and what the
+	/// program wrote.
+	fn session(lines: &str) -> (String, String) {
+		let mut lagoon: Lagoon<i32> = Lagoon::new();
+		lagoon.gen_pools();
+		let (io, output) = Io::memory("");
+		lagoon.pools.io = io;
+		let mut input = Input::new(Cursor::new(lines.to_string()));
+		let mut out = Buffer::new();
+		super::run(&mut lagoon, &mut input, &mut out).unwrap();
+		(out.contents(), output.contents())
+	}
+
+	#[test]
+	fn state_carries_over() {
+		let (out, output) = session("0+ 1+;\n0+;\n1o;\n:mem\n:pools\n");
+		assert_eq!(out, "> > > > 0: 1\n1: 3\n> +: 1\no: 1\n> \n");
+		assert_eq!(output, "3\n");
+	}
+
+	#[test]
+	fn multi_line() {
+		let (out, output) = session("0+;\n0+ 0- 0o {0|\n  ;\n}\n");
+		assert_eq!(out, "> > . . > \n");
+		assert_eq!(output, "0\n");
+	}
+
+	#[test]
+	fn reset() {
+		let (out, _) = session("0+;\n:reset\n:mem\n:pools\n");
+		assert_eq!(out, "> > > > > \n");
+	}
+
+	#[test]
+	fn errors() {
+		let (out, _) = session("0+ }\n0x;\n:nope\n{0|\n");
+		assert_eq!(
+			out,
+			"> 1:4: unmatched `}`\n\
+			> 1:1: pool `x` is not declared\n\
+			> unknown command `:nope`, try :help\n\
+			> . \n1:1: unclosed `{`\n"
+		);
+	}
+
+	#[test]
+	fn quit() {
+		let (out, output) = session(":quit\n0+ 0o;\n");
+		assert_eq!(out, "> ");
+		assert_eq!(output, "");
+	}
+}